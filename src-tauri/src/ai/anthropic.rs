@@ -0,0 +1,496 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Instant;
+
+use super::models::{ModelCache, ModelsResponse};
+use super::provider::{AIError, AIProvider, ProviderDiagnostics};
+use super::secret::SecretString;
+use super::types::{AIResponse, ChatMessage, GenerationOptions, ModelInfo, TokenUsage};
+
+/// Messages API version sent with every request
+const API_VERSION: &str = "2023-06-01";
+
+/// `max_tokens` is required by the Messages API
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Request body for the Messages API
+#[derive(Debug, Serialize)]
+struct MessagesRequest {
+    model: String,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct AnthropicMessage {
+    role: &'static str,
+    content: String,
+}
+
+/// Response from the Messages API
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    #[serde(default)]
+    model: Option<String>,
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: Option<u32>,
+    #[serde(default)]
+    output_tokens: Option<u32>,
+}
+
+/// Anthropic provider using the Messages API with an API key
+pub struct AnthropicProvider {
+    client: Client,
+    base_url: String,
+    model: String,
+    api_key: SecretString,
+    models: ModelCache,
+}
+
+impl AnthropicProvider {
+    /// Create a new Anthropic provider
+    pub fn new(api_key: SecretString, model: Option<String>) -> Result<Self, AIError> {
+        if api_key.is_empty() {
+            return Err(AIError::InvalidConfig(
+                "Anthropic API key is required".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            client: Client::new(),
+            base_url: "https://api.anthropic.com".to_string(),
+            model: model.unwrap_or_else(|| "claude-sonnet-4-20250514".to_string()),
+            api_key,
+            models: ModelCache::default(),
+        })
+    }
+
+    fn messages_url(&self) -> String {
+        format!("{}/v1/messages", self.base_url.trim_end_matches('/'))
+    }
+
+    fn models_url(&self) -> String {
+        format!("{}/v1/models", self.base_url.trim_end_matches('/'))
+    }
+
+    /// Request the model list, bypassing the cache
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        let response = self
+            .client
+            .get(self.models_url())
+            .header("x-api-key", self.api_key.expose())
+            .header("anthropic-version", API_VERSION)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AIError::from_response("Anthropic models", response, Some(&self.api_key)).await);
+        }
+
+        let body: ModelsResponse = response.json().await?;
+        Ok(body.data)
+    }
+
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: String,
+        options: &GenerationOptions,
+        stream: bool,
+    ) -> MessagesRequest {
+        let (system, messages) = convert_messages(messages);
+        MessagesRequest {
+            model,
+            max_tokens: options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            system,
+            messages,
+            temperature: options.temperature,
+            stream,
+        }
+    }
+
+    async fn send(&self, request: &MessagesRequest) -> Result<reqwest::Response, AIError> {
+        let response = self
+            .client
+            .post(self.messages_url())
+            .header("x-api-key", self.api_key.expose())
+            .header("anthropic-version", API_VERSION)
+            .header("content-type", "application/json")
+            .json(request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AIError::from_response("Anthropic API", response, Some(&self.api_key)).await);
+        }
+        Ok(response)
+    }
+}
+
+/// Split out system messages and fit the rest to the Messages API
+///
+/// System messages go into the top-level `system` field. Roles other than
+/// "assistant" become "user", and consecutive messages with the same role
+/// are merged since the API requires alternating turns.
+fn convert_messages(messages: Vec<ChatMessage>) -> (Option<String>, Vec<AnthropicMessage>) {
+    let mut system: Vec<String> = Vec::new();
+    let mut converted: Vec<AnthropicMessage> = Vec::new();
+
+    for message in messages {
+        let role = match message.role.as_str() {
+            "system" => {
+                system.push(message.content);
+                continue;
+            }
+            "assistant" => "assistant",
+            _ => "user",
+        };
+
+        match converted.last_mut() {
+            Some(last) if last.role == role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            }
+            _ => converted.push(AnthropicMessage {
+                role,
+                content: message.content,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system, converted)
+}
+
+fn token_usage(usage: &AnthropicUsage) -> Option<TokenUsage> {
+    if usage.input_tokens.is_none() && usage.output_tokens.is_none() {
+        return None;
+    }
+    Some(TokenUsage {
+        prompt_tokens: usage.input_tokens.unwrap_or(0),
+        completion_tokens: usage.output_tokens.unwrap_or(0),
+    })
+}
+
+#[async_trait]
+impl AIProvider for AnthropicProvider {
+    async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
+        self.complete_with_model(messages, None).await
+    }
+
+    async fn complete_with_model(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+    ) -> Result<AIResponse, AIError> {
+        self.complete_with_options(messages, model, &GenerationOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        let model_to_use = model.unwrap_or_else(|| self.model.clone());
+        let request = self.build_request(messages, model_to_use.clone(), options, false);
+
+        let completion: MessagesResponse = self.send(&request).await?.json().await?;
+
+        // Concatenate every text block (tool/thinking blocks are skipped)
+        let content: String = completion
+            .content
+            .iter()
+            .filter(|block| block.kind == "text")
+            .filter_map(|block| block.text.as_deref())
+            .collect();
+
+        let usage = completion.usage.as_ref().and_then(token_usage);
+
+        Ok(AIResponse {
+            content,
+            provider: "anthropic".to_string(),
+            tokens: usage.map(|u| u.total()),
+            model: Some(completion.model.unwrap_or(model_to_use)),
+            usage,
+            route: None,
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+        on_delta: &(dyn for<'a> Fn(&'a str) + Send + Sync),
+    ) -> Result<AIResponse, AIError> {
+        let model_to_use = model.unwrap_or_else(|| self.model.clone());
+        let request = self.build_request(messages, model_to_use.clone(), options, true);
+
+        let mut response = self.send(&request).await?;
+        let mut stream = StreamState::default();
+        let mut buffer: Vec<u8> = Vec::new();
+
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+
+            // Server-sent events are separated by a blank line
+            while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = buffer.drain(..end + 2).collect();
+                stream.handle_event(&String::from_utf8_lossy(&event), on_delta)?;
+            }
+        }
+        if !buffer.is_empty() {
+            stream.handle_event(&String::from_utf8_lossy(&buffer), on_delta)?;
+        }
+
+        let usage = token_usage(&stream.usage);
+        Ok(AIResponse {
+            content: stream.content,
+            provider: "anthropic".to_string(),
+            tokens: usage.map(|u| u.total()),
+            model: Some(stream.model.unwrap_or(model_to_use)),
+            usage,
+            route: None,
+        })
+    }
+
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn configured_model(&self) -> Option<String> {
+        Some(self.model.clone())
+    }
+
+    async fn is_available(&self) -> bool {
+        // Available if we have an API key; no ping to avoid charges
+        !self.api_key.is_empty()
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        self.models.get_or_fetch(self.fetch_models()).await
+    }
+
+    async fn diagnose(&self) -> ProviderDiagnostics {
+        let started = Instant::now();
+        let result = self.fetch_models().await;
+        if let Ok(models) = &result {
+            self.models.set(models.clone());
+        }
+        ProviderDiagnostics::from_models(self.name(), Some(&self.model), result, started)
+    }
+}
+
+/// Accumulated state of a streamed Messages API response
+#[derive(Default)]
+struct StreamState {
+    content: String,
+    model: Option<String>,
+    usage: AnthropicUsage,
+}
+
+impl StreamState {
+    /// Apply one server-sent event
+    fn handle_event(
+        &mut self,
+        event: &str,
+        on_delta: &(dyn for<'a> Fn(&'a str) + Send + Sync),
+    ) -> Result<(), AIError> {
+        let data: String = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(str::trim_start)
+            .collect();
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let data: Value = serde_json::from_str(&data)?;
+        match data["type"].as_str() {
+            Some("message_start") => {
+                let message = &data["message"];
+                self.model = message["model"].as_str().map(str::to_string);
+                if let Some(tokens) = message["usage"]["input_tokens"].as_u64() {
+                    self.usage.input_tokens = Some(tokens as u32);
+                }
+            }
+            Some("content_block_delta") if data["delta"]["type"] == "text_delta" => {
+                if let Some(text) = data["delta"]["text"].as_str() {
+                    self.content.push_str(text);
+                    on_delta(text);
+                }
+            }
+            Some("message_delta") => {
+                if let Some(tokens) = data["usage"]["output_tokens"].as_u64() {
+                    self.usage.output_tokens = Some(tokens as u32);
+                }
+            }
+            Some("error") => {
+                return Err(AIError::ApiError(format!(
+                    "Anthropic stream error: {}",
+                    data["error"]["message"].as_str().unwrap_or("unknown error")
+                )));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{anthropic_message, user_message, StubResponse, StubServer};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    const KEY: &str = "sk-ant-test-key";
+
+    fn provider(server: &StubServer) -> AnthropicProvider {
+        let mut provider = AnthropicProvider::new(SecretString::new(KEY), Some("claude-test".into())).unwrap();
+        provider.base_url = server.url().to_string();
+        provider
+    }
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn moves_system_prompt_and_merges_roles() {
+        let (system, messages) = convert_messages(vec![
+            message("system", "Be brief."),
+            message("user", "Write a hook."),
+            message("user", "About rain."),
+            message("assistant", "Rain on the roof"),
+            message("system", "Use rhymes."),
+            message("user", "Again"),
+        ]);
+
+        assert_eq!(system.as_deref(), Some("Be brief.\n\nUse rhymes."));
+        assert_eq!(
+            messages,
+            vec![
+                AnthropicMessage { role: "user", content: "Write a hook.\n\nAbout rain.".into() },
+                AnthropicMessage { role: "assistant", content: "Rain on the roof".into() },
+                AnthropicMessage { role: "user", content: "Again".into() },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_messages_request_and_joins_text_blocks() {
+        let server = StubServer::start().await;
+        let mut body = anthropic_message("Verse one, ", "claude-test");
+        body["content"]
+            .as_array_mut()
+            .unwrap()
+            .extend([json!({ "type": "thinking", "thinking": "..." }), json!({ "type": "text", "text": "verse two" })]);
+        server.on("POST", "/v1/messages", StubResponse::json(200, body));
+
+        let mut messages = user_message("hi");
+        messages.insert(0, message("system", "You are a lyricist."));
+        let response = provider(&server).complete(messages).await.unwrap();
+
+        assert_eq!(response.content, "Verse one, verse two");
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 8));
+
+        let request = &server.requests_to("/v1/messages")[0];
+        assert_eq!(request.headers["x-api-key"], KEY);
+        assert_eq!(request.headers["anthropic-version"], API_VERSION);
+        let body = request.json();
+        assert_eq!(body["system"], "You are a lyricist.");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert!(body.get("stream").is_none());
+    }
+
+    #[tokio::test]
+    async fn error_messages_redact_the_api_key() {
+        let server = StubServer::start().await;
+        server.on(
+            "POST",
+            "/v1/messages",
+            StubResponse::json(529, json!({ "type": "error", "error": { "message": format!("overloaded for {}", KEY) } })),
+        );
+
+        let error = provider(&server).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Http { status: 529, .. }));
+        assert!(!error.to_string().contains(KEY));
+    }
+
+    #[tokio::test]
+    async fn streams_text_deltas() {
+        let events = [
+            json!({ "type": "message_start", "message": { "model": "claude-test", "usage": { "input_tokens": 9, "output_tokens": 1 } } }),
+            json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "text", "text": "" } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "Hello" } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": " world" } }),
+            json!({ "type": "message_delta", "delta": { "stop_reason": "end_turn" }, "usage": { "output_tokens": 4 } }),
+            json!({ "type": "message_stop" }),
+        ];
+        let sse: String = events
+            .iter()
+            .map(|e| format!("event: {}\ndata: {}\n\n", e["type"].as_str().unwrap(), e))
+            .collect();
+
+        let server = StubServer::start().await;
+        server.on("POST", "/v1/messages", StubResponse::raw(200, &sse));
+
+        let deltas = Mutex::new(Vec::new());
+        let on_delta = |text: &str| deltas.lock().unwrap().push(text.to_string());
+        let response = provider(&server)
+            .complete_stream(user_message("hi"), None, &GenerationOptions::default(), &on_delta)
+            .await
+            .unwrap();
+
+        assert_eq!(*deltas.lock().unwrap(), vec!["Hello", " world"]);
+        assert_eq!(response.content, "Hello world");
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (9, 4));
+        assert_eq!(server.requests()[0].json()["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn stream_errors_are_reported() {
+        let server = StubServer::start().await;
+        let error = json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } });
+        server.on("POST", "/v1/messages", StubResponse::raw(200, &format!("event: error\ndata: {}\n\n", error)));
+
+        let result = provider(&server)
+            .complete_stream(user_message("hi"), None, &GenerationOptions::default(), &|_| {})
+            .await;
+
+        assert!(result.unwrap_err().to_string().contains("Overloaded"));
+    }
+}
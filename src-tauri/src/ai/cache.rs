@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::storage::{now_millis, read_json, write_json};
+use super::types::{AIResponse, ChatMessage, GenerationOptions, ResponseFormat};
+
+/// Response cache settings (part of `AIConfig`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CachePolicy {
+    /// Serve repeated requests from the on-disk cache
+    pub enabled: bool,
+    /// How long an entry stays valid, in seconds
+    pub ttl_secs: u64,
+    /// Maximum number of cached responses
+    pub max_entries: usize,
+    /// Maximum total size of the cache directory, in bytes
+    pub max_bytes: u64,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: 7 * 24 * 60 * 60,
+            max_entries: 1_000,
+            max_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// Cache size report
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+/// Everything that determines a response; hashed into the cache key
+#[derive(Serialize)]
+struct CacheKey<'a> {
+    provider: &'a str,
+    model: Option<&'a str>,
+    messages: &'a [ChatMessage],
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    response_format: Option<&'a ResponseFormat>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Write time (Unix millis)
+    created_at: u64,
+    response: AIResponse,
+}
+
+/// Content-addressed response cache (one JSON file per request hash)
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    /// Create a cache rooted at `dir` (created on first write)
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Hash a request into a cache key
+    pub fn key(
+        provider: &str,
+        model: Option<&str>,
+        messages: &[ChatMessage],
+        options: &GenerationOptions,
+    ) -> String {
+        let key = CacheKey {
+            provider,
+            model,
+            messages,
+            temperature: options.temperature,
+            max_tokens: options.max_tokens,
+            response_format: options.response_format.as_ref(),
+        };
+        // Serializing plain structs and BTreeMap-backed JSON values is deterministic
+        let bytes = serde_json::to_vec(&key).unwrap_or_default();
+        Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Look up a cached response that is still within the TTL
+    pub fn get(&self, key: &str, policy: &CachePolicy) -> Option<AIResponse> {
+        let path = self.path(key);
+        let entry = match read_json::<CacheEntry>(&path) {
+            Ok(entry) => entry?,
+            Err(e) => {
+                eprintln!("[AI] Discarding cache entry: {}", e);
+                let _ = std::fs::remove_file(&path);
+                return None;
+            }
+        };
+
+        if now_millis().saturating_sub(entry.created_at) > policy.ttl_secs * 1000 {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+
+        Some(entry.response)
+    }
+
+    /// Store a response, then evict entries over the policy limits
+    pub fn put(&self, key: &str, response: &AIResponse, policy: &CachePolicy) {
+        let entry = CacheEntry {
+            created_at: now_millis(),
+            response: AIResponse {
+                route: None,
+                ..response.clone()
+            },
+        };
+
+        // Caching is best-effort: never fail the completion over it
+        if let Err(e) = write_json(&self.path(key), &entry) {
+            eprintln!("[AI] Failed to cache response: {}", e);
+            return;
+        }
+        self.evict(policy);
+    }
+
+    /// Remove expired entries, then the oldest until within the limits
+    fn evict(&self, policy: &CachePolicy) {
+        let mut files = self.files();
+        let now = SystemTime::now();
+        let ttl = std::time::Duration::from_secs(policy.ttl_secs);
+
+        files.retain(|(path, _, modified)| {
+            let expired = now.duration_since(*modified).is_ok_and(|age| age > ttl);
+            if expired {
+                let _ = std::fs::remove_file(path);
+            }
+            !expired
+        });
+
+        // Oldest first
+        files.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut count = files.len();
+        let mut bytes: u64 = files.iter().map(|(_, len, _)| len).sum();
+        for (path, len, _) in files {
+            if count <= policy.max_entries && bytes <= policy.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                count -= 1;
+                bytes -= len;
+            }
+        }
+    }
+
+    /// Cache entries as (path, size, modification time)
+    fn files(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| is_entry(path))
+            .filter_map(|path| {
+                let metadata = std::fs::metadata(&path).ok()?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((path, metadata.len(), modified))
+            })
+            .collect()
+    }
+
+    /// Report the number and total size of cached responses
+    pub fn stats(&self) -> CacheStats {
+        let files = self.files();
+        CacheStats {
+            entries: files.len(),
+            bytes: files.iter().map(|(_, len, _)| len).sum(),
+        }
+    }
+
+    /// Delete all cached responses
+    pub fn clear(&self) -> Result<(), String> {
+        for (path, _, _) in self.files() {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to clear cache: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_entry(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("json")
+}
@@ -0,0 +1,16 @@
+use std::sync::Arc;
+use tauri::State;
+
+use super::cache::{CacheStats, ResponseCache};
+
+/// Tauri command to report the response cache size
+#[tauri::command]
+pub async fn ai_cache_stats(cache: State<'_, Arc<ResponseCache>>) -> Result<CacheStats, String> {
+    Ok(cache.stats())
+}
+
+/// Tauri command to delete all cached AI responses
+#[tauri::command]
+pub async fn ai_cache_clear(cache: State<'_, Arc<ResponseCache>>) -> Result<(), String> {
+    cache.clear()
+}
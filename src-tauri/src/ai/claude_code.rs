@@ -1,10 +1,26 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::path::PathBuf;
+use tokio::sync::Mutex;
 
 use super::provider::{AIError, AIProvider};
-use super::types::{AIResponse, ChatMessage};
+use super::secret::SecretString;
+use super::storage::{now_millis, write_private_atomic};
+use super::types::{AIResponse, ChatMessage, TokenUsage};
+
+/// OAuth token endpoint used by the Claude Code CLI
+const TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+
+/// Public OAuth client id of the Claude Code CLI
+const CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+
+/// Refresh tokens this long before they expire
+const REFRESH_MARGIN_MS: i64 = 5 * 60 * 1000;
+
+/// Warn users when credentials lapse within this window and can't be refreshed
+const EXPIRY_WARNING_MS: i64 = 24 * 60 * 60 * 1000;
 
 /// Claude Code credentials from ~/.claude/.credentials.json
 #[derive(Debug, Deserialize)]
@@ -16,13 +32,59 @@ struct ClaudeCredentials {
 #[derive(Debug, Deserialize, Clone)]
 struct OAuthTokens {
     #[serde(rename = "accessToken")]
-    access_token: String,
+    access_token: SecretString,
     #[serde(rename = "refreshToken")]
-    refresh_token: String,
+    refresh_token: SecretString,
+    /// Expiry time; the CLI writes Unix millis, older files used seconds
     #[serde(rename = "expiresAt")]
     expires_at: i64,
 }
 
+impl OAuthTokens {
+    /// Expiry time in Unix millis
+    fn expires_at_ms(&self) -> i64 {
+        // Anything below ~2001 in millis must be a seconds timestamp
+        if self.expires_at < 1_000_000_000_000 {
+            self.expires_at * 1000
+        } else {
+            self.expires_at
+        }
+    }
+
+    /// Whether the access token is expired or about to expire
+    fn is_expired(&self) -> bool {
+        self.expires_at_ms() < now_millis() as i64 + REFRESH_MARGIN_MS
+    }
+}
+
+/// Response from the OAuth token endpoint
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: SecretString,
+    /// Omitted when the server keeps the current refresh token
+    #[serde(default)]
+    refresh_token: Option<SecretString>,
+    /// Lifetime of the access token in seconds
+    expires_in: i64,
+}
+
+/// Credential status for the settings UI
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaudeCodeAuthStatus {
+    /// Credentials file has OAuth tokens
+    pub logged_in: bool,
+    /// Access token expiry (Unix millis)
+    pub expires_at: Option<i64>,
+    /// Milliseconds until expiry (negative once expired)
+    pub expires_in_ms: Option<i64>,
+    pub expired: bool,
+    /// A refresh token is available to renew the access token
+    pub can_refresh: bool,
+    /// Credentials will lapse soon and need attention
+    pub needs_attention: bool,
+    pub error: Option<String>,
+}
+
 /// Request body for Claude API
 #[derive(Debug, Serialize)]
 struct ClaudeRequest {
@@ -60,6 +122,10 @@ pub struct ClaudeCodeProvider {
     client: Client,
     model: String,
     credentials_path: PathBuf,
+    api_url: String,
+    token_url: String,
+    /// Serializes refreshes so concurrent requests don't race on the file
+    refresh_lock: Mutex<()>,
 }
 
 impl ClaudeCodeProvider {
@@ -73,6 +139,9 @@ impl ClaudeCodeProvider {
             client: Client::new(),
             model: model.unwrap_or_else(|| "claude-sonnet-4-20250514".to_string()),
             credentials_path,
+            api_url: "https://api.anthropic.com/v1/messages".to_string(),
+            token_url: TOKEN_URL.to_string(),
+            refresh_lock: Mutex::new(()),
         }
     }
 
@@ -95,29 +164,119 @@ impl ClaudeCodeProvider {
         })
     }
 
-    /// Check if token is expired
-    fn is_token_expired(&self, tokens: &OAuthTokens) -> bool {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_secs() as i64)
-            .unwrap_or(0);
+    /// Get valid tokens, refreshing them first if they are about to expire
+    async fn valid_tokens(&self) -> Result<OAuthTokens, AIError> {
+        let tokens = self.load_credentials()?;
+        if !tokens.is_expired() {
+            return Ok(tokens);
+        }
 
-        // Consider expired if less than 5 minutes remaining
-        tokens.expires_at < (now + 300)
-    }
-}
+        let _guard = self.refresh_lock.lock().await;
 
-#[async_trait]
-impl AIProvider for ClaudeCodeProvider {
-    async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
+        // Another request may have refreshed while we waited
         let tokens = self.load_credentials()?;
+        if !tokens.is_expired() {
+            return Ok(tokens);
+        }
+
+        self.refresh(&tokens).await
+    }
 
-        if self.is_token_expired(&tokens) {
+    /// Exchange the refresh token for new tokens and persist them
+    async fn refresh(&self, tokens: &OAuthTokens) -> Result<OAuthTokens, AIError> {
+        if tokens.refresh_token.is_empty() {
             return Err(AIError::InvalidConfig(
                 "Claude Code token expired. Please re-login with 'claude' CLI.".to_string(),
             ));
         }
 
+        let response = self
+            .client
+            .post(&self.token_url)
+            .json(&json!({
+                "grant_type": "refresh_token",
+                "refresh_token": tokens.refresh_token.expose(),
+                "client_id": CLIENT_ID,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error =
+                AIError::from_response("Claude Code token refresh", response, Some(&tokens.refresh_token))
+                    .await;
+            return Err(AIError::InvalidConfig(format!(
+                "{}. Please re-login with 'claude' CLI.",
+                error
+            )));
+        }
+
+        let body: TokenResponse = response.json().await?;
+        let refreshed = OAuthTokens {
+            access_token: body.access_token,
+            refresh_token: body.refresh_token.unwrap_or_else(|| tokens.refresh_token.clone()),
+            expires_at: now_millis() as i64 + body.expires_in * 1000,
+        };
+
+        self.save_tokens(&refreshed)?;
+        eprintln!("[AI] Refreshed Claude Code OAuth token");
+        Ok(refreshed)
+    }
+
+    /// Write tokens back to the credentials file, keeping its other fields
+    fn save_tokens(&self, tokens: &OAuthTokens) -> Result<(), AIError> {
+        let content = std::fs::read_to_string(&self.credentials_path)
+            .map_err(|e| AIError::InvalidConfig(format!("Failed to read credentials: {}", e)))?;
+        let mut credentials: Value = serde_json::from_str(&content)?;
+
+        let oauth = credentials
+            .get_mut("claudeAiOauth")
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| AIError::InvalidConfig("No OAuth tokens found in credentials".to_string()))?;
+        oauth.insert("accessToken".to_string(), json!(tokens.access_token.expose()));
+        oauth.insert("refreshToken".to_string(), json!(tokens.refresh_token.expose()));
+        oauth.insert("expiresAt".to_string(), json!(tokens.expires_at));
+
+        let json = serde_json::to_vec_pretty(&credentials)?;
+        write_private_atomic(&self.credentials_path, &json)
+            .map_err(|e| AIError::InvalidConfig(format!("Failed to save credentials: {}", e)))
+    }
+
+    /// Describe the stored credentials without touching the network
+    pub fn auth_status(&self) -> ClaudeCodeAuthStatus {
+        match self.load_credentials() {
+            Ok(tokens) => {
+                let expires_at = tokens.expires_at_ms();
+                let expires_in = expires_at - now_millis() as i64;
+                let can_refresh = !tokens.refresh_token.is_empty();
+                ClaudeCodeAuthStatus {
+                    logged_in: true,
+                    expires_at: Some(expires_at),
+                    expires_in_ms: Some(expires_in),
+                    expired: expires_in <= 0,
+                    can_refresh,
+                    needs_attention: !can_refresh && expires_in < EXPIRY_WARNING_MS,
+                    error: None,
+                }
+            }
+            Err(e) => ClaudeCodeAuthStatus {
+                logged_in: false,
+                expires_at: None,
+                expires_in_ms: None,
+                expired: false,
+                can_refresh: false,
+                needs_attention: true,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl AIProvider for ClaudeCodeProvider {
+    async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
+        let tokens = self.valid_tokens().await?;
+
         // Convert messages to Claude format
         let claude_messages: Vec<ClaudeMessage> = messages
             .into_iter()
@@ -135,8 +294,8 @@ impl AIProvider for ClaudeCodeProvider {
 
         let response = self
             .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &tokens.access_token)
+            .post(&self.api_url)
+            .header("x-api-key", tokens.access_token.expose())
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
             .json(&request)
@@ -144,12 +303,7 @@ impl AIProvider for ClaudeCodeProvider {
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AIError::ApiError(format!(
-                "Claude API error ({}): {}",
-                status, error_text
-            )));
+            return Err(AIError::from_response("Claude API", response, Some(&tokens.access_token)).await);
         }
 
         let completion: ClaudeResponse = response.json().await?;
@@ -160,14 +314,18 @@ impl AIProvider for ClaudeCodeProvider {
             .and_then(|c| c.text.clone())
             .unwrap_or_default();
 
-        let tokens_used = completion.usage.map(|u| {
-            u.input_tokens.unwrap_or(0) + u.output_tokens.unwrap_or(0)
+        let usage = completion.usage.map(|u| TokenUsage {
+            prompt_tokens: u.input_tokens.unwrap_or(0),
+            completion_tokens: u.output_tokens.unwrap_or(0),
         });
 
         Ok(AIResponse {
             content,
             provider: "claude-code".to_string(),
-            tokens: tokens_used,
+            tokens: usage.map(|u| u.total()),
+            model: Some(self.model.clone()),
+            usage,
+            route: None,
         })
     }
 
@@ -175,10 +333,119 @@ impl AIProvider for ClaudeCodeProvider {
         "claude-code"
     }
 
+    fn configured_model(&self) -> Option<String> {
+        Some(self.model.clone())
+    }
+
     async fn is_available(&self) -> bool {
+        // Expired tokens still count if they can be refreshed on the next request
         match self.load_credentials() {
-            Ok(tokens) => !self.is_token_expired(&tokens),
+            Ok(tokens) => !tokens.is_expired() || !tokens.refresh_token.is_empty(),
             Err(_) => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{anthropic_message, user_message, StubResponse, StubServer, TempDir};
+
+    /// Provider with a temp credentials file pointed at a stub server
+    fn provider(server: &StubServer, expires_at: i64) -> (ClaudeCodeProvider, TempDir) {
+        let dir = TempDir::new("claude");
+        let credentials_path = dir.join(".credentials.json");
+        let credentials = json!({
+            "claudeAiOauth": {
+                "accessToken": "old-access",
+                "refreshToken": "old-refresh",
+                "expiresAt": expires_at,
+                "scopes": ["user:inference"]
+            },
+            "otherSetting": true
+        });
+        std::fs::write(&credentials_path, credentials.to_string()).unwrap();
+
+        let provider = ClaudeCodeProvider {
+            client: Client::new(),
+            model: "claude-test".to_string(),
+            credentials_path,
+            api_url: format!("{}/v1/messages", server.url()),
+            token_url: format!("{}/v1/oauth/token", server.url()),
+            refresh_lock: Mutex::new(()),
+        };
+        (provider, dir)
+    }
+
+    fn read_credentials(provider: &ClaudeCodeProvider) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(&provider.credentials_path).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn refreshes_expired_tokens_and_writes_them_back() {
+        let server = StubServer::start().await;
+        server.on(
+            "POST",
+            "/v1/oauth/token",
+            StubResponse::json(
+                200,
+                json!({ "access_token": "new-access", "refresh_token": "new-refresh", "expires_in": 3600 }),
+            ),
+        );
+        server.on("POST", "/v1/messages", StubResponse::json(200, anthropic_message("hi", "claude-test")));
+
+        let (provider, _dir) = provider(&server, now_millis() as i64 - 1000);
+        assert!(provider.is_available().await, "refreshable tokens count as available");
+
+        provider.complete(user_message("hi")).await.unwrap();
+
+        let refresh = server.requests_to("/v1/oauth/token")[0].json();
+        assert_eq!(refresh["grant_type"], "refresh_token");
+        assert_eq!(refresh["refresh_token"], "old-refresh");
+        assert_eq!(server.requests_to("/v1/messages")[0].headers["x-api-key"], "new-access");
+
+        let saved = read_credentials(&provider);
+        assert_eq!(saved["claudeAiOauth"]["accessToken"], "new-access");
+        assert_eq!(saved["claudeAiOauth"]["refreshToken"], "new-refresh");
+        assert_eq!(saved["claudeAiOauth"]["scopes"][0], "user:inference");
+        assert_eq!(saved["otherSetting"], true);
+
+        let status = provider.auth_status();
+        assert!(!status.expired);
+        assert!(status.expires_in_ms.unwrap() > 3_500_000);
+    }
+
+    #[tokio::test]
+    async fn valid_tokens_are_not_refreshed() {
+        let server = StubServer::start().await;
+        server.on("POST", "/v1/messages", StubResponse::json(200, anthropic_message("hi", "claude-test")));
+
+        let (provider, _dir) = provider(&server, now_millis() as i64 + 3_600_000);
+        provider.complete(user_message("hi")).await.unwrap();
+
+        assert!(server.requests_to("/v1/oauth/token").is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_asks_for_relogin() {
+        let server = StubServer::start().await;
+        server.on("POST", "/v1/oauth/token", StubResponse::json(400, json!({ "error": "invalid_grant" })));
+
+        let (provider, _dir) = provider(&server, now_millis() as i64 - 1000);
+        let error = provider.complete(user_message("hi")).await.unwrap_err();
+
+        assert!(error.to_string().contains("re-login"));
+        assert!(server.requests_to("/v1/messages").is_empty());
+        assert_eq!(read_credentials(&provider)["claudeAiOauth"]["accessToken"], "old-access");
+    }
+
+    #[test]
+    fn reads_second_based_expiry() {
+        let tokens = OAuthTokens {
+            access_token: SecretString::new("a"),
+            refresh_token: SecretString::new("r"),
+            expires_at: 1_700_000_000,
+        };
+        assert_eq!(tokens.expires_at_ms(), 1_700_000_000_000);
+    }
+}
@@ -1,45 +1,120 @@
 use async_trait::async_trait;
 use reqwest::Client;
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-use super::provider::{AIError, AIProvider};
-use super::types::{AIResponse, ChatCompletionRequest, ChatCompletionResponse, ChatMessage};
+use super::models::{ModelCache, ModelsResponse};
+use super::provider::{AIError, AIProvider, ProviderDiagnostics};
+use super::types::{
+    AIResponse, ChatCompletionRequest, ChatCompletionResponse, ChatMessage, GenerationOptions,
+    ModelInfo,
+};
+
+/// Port the managed CLIProxyAPI server uses unless it is taken
+pub const DEFAULT_PORT: u16 = 8317;
+
+/// CLIProxyAPI base URL shared between the process manager and the provider
+///
+/// The manager updates it when the server moves to another port, so the
+/// provider always talks to the port the server actually listens on.
+#[derive(Debug, Clone)]
+pub struct ServerUrl(Arc<RwLock<String>>);
+
+impl ServerUrl {
+    pub fn new(url: String) -> Self {
+        Self(Arc::new(RwLock::new(url)))
+    }
+
+    /// Current URL
+    pub fn get(&self) -> String {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, url: String) {
+        *self.0.write().unwrap() = url;
+    }
+}
 
 /// CLIProxyAPI provider - connects to external CLIProxyAPI server
 /// User runs CLIProxyAPI separately and updates it independently
 /// Server provides access to Claude Code, Gemini CLI, Codex CLI via OAuth subscriptions
 pub struct CLIProxyAPIProvider {
     client: Client,
-    base_url: String,
-    model: String,
+    base_url: ServerUrl,
+    model: Option<String>,
+    models: ModelCache,
 }
 
 impl CLIProxyAPIProvider {
     /// Create a new CLIProxyAPI provider
     ///
     /// # Arguments
-    /// * `base_url` - CLIProxyAPI server URL (default: http://localhost:8080)
-    /// * `model` - Model to use (e.g., "claude-sonnet-4", "gemini-2.0-flash", "gpt-4o")
+    /// * `base_url` - CLIProxyAPI server URL (default: `CLIPROXYAPI_URL`, then
+    ///   `http://localhost:{DEFAULT_PORT}`)
+    /// * `model` - Model to use (e.g., "claude-sonnet-4", "gemini-2.0-flash", "gpt-4o");
+    ///   when unset, the first model reported by `/v1/models` is used
     pub fn new(base_url: Option<String>, model: Option<String>) -> Self {
+        let base_url = base_url.unwrap_or_else(|| {
+            std::env::var("CLIPROXYAPI_URL")
+                .unwrap_or_else(|_| format!("http://localhost:{}", DEFAULT_PORT))
+        });
+        Self::with_server_url(ServerUrl::new(base_url), model)
+    }
+
+    /// Create a provider for the managed server, following its URL as it changes
+    pub fn with_server_url(base_url: ServerUrl, model: Option<String>) -> Self {
         Self {
             client: Client::builder()
                 .timeout(Duration::from_secs(120))
                 .build()
                 .unwrap_or_else(|_| Client::new()),
-            base_url: base_url.unwrap_or_else(|| {
-                std::env::var("CLIPROXYAPI_URL")
-                    .unwrap_or_else(|_| "http://localhost:8080".to_string())
-            }),
-            model: model.unwrap_or_else(|| {
-                std::env::var("CLIPROXYAPI_MODEL")
-                    .unwrap_or_else(|_| "claude-sonnet-4".to_string())
-            }),
+            base_url,
+            model: model.or_else(|| std::env::var("CLIPROXYAPI_MODEL").ok()),
+            models: ModelCache::default(),
         }
     }
 
     /// Get the OpenAI-compatible chat completions endpoint
     fn endpoint_url(&self) -> String {
-        format!("{}/v1/chat/completions", self.base_url.trim_end_matches('/'))
+        format!("{}/v1/chat/completions", self.base_url.get().trim_end_matches('/'))
+    }
+
+    /// Get the OpenAI-compatible models endpoint
+    fn models_url(&self) -> String {
+        format!("{}/v1/models", self.base_url.get().trim_end_matches('/'))
+    }
+
+    /// Request the model list, bypassing the cache
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        let response = self
+            .client
+            .get(self.models_url())
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AIError::from_response("CLIProxyAPI models", response, None).await);
+        }
+
+        let body: ModelsResponse = response.json().await?;
+        Ok(body.data)
+    }
+
+    /// Pick the model for a request: override, configured, then first discovered
+    async fn resolve_model(&self, model: Option<String>) -> Result<String, AIError> {
+        if let Some(model) = model.or_else(|| self.model.clone()) {
+            return Ok(model);
+        }
+
+        self.list_models()
+            .await?
+            .into_iter()
+            .next()
+            .map(|m| m.id)
+            .ok_or_else(|| {
+                AIError::InvalidConfig("No model configured and CLIProxyAPI reported none".to_string())
+            })
     }
 }
 
@@ -54,13 +129,24 @@ impl AIProvider for CLIProxyAPIProvider {
         messages: Vec<ChatMessage>,
         model: Option<String>,
     ) -> Result<AIResponse, AIError> {
-        let model_to_use = model.unwrap_or_else(|| self.model.clone());
+        self.complete_with_options(messages, model, &GenerationOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        let model_to_use = self.resolve_model(model).await?;
 
         let request = ChatCompletionRequest {
             model: model_to_use.clone(),
             messages,
-            temperature: Some(0.7),
-            max_tokens: Some(4096),
+            temperature: Some(options.temperature.unwrap_or(0.7)),
+            max_tokens: Some(options.max_tokens.unwrap_or(4096)),
+            response_format: options.response_format.clone(),
         };
 
         let response = self
@@ -72,12 +158,7 @@ impl AIProvider for CLIProxyAPIProvider {
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AIError::ApiError(format!(
-                "CLIProxyAPI error ({}): {}",
-                status, error_text
-            )));
+            return Err(AIError::from_response("CLIProxyAPI", response, None).await);
         }
 
         let completion: ChatCompletionResponse = response.json().await?;
@@ -90,7 +171,10 @@ impl AIProvider for CLIProxyAPIProvider {
         Ok(AIResponse {
             content: choice.message.content.clone(),
             provider: format!("cliproxyapi:{}", model_to_use),
-            tokens: completion.usage.and_then(|u| u.total_tokens),
+            tokens: completion.usage.as_ref().and_then(|u| u.total_tokens),
+            model: Some(model_to_use),
+            usage: completion.usage.as_ref().and_then(|u| u.token_usage()),
+            route: None,
         })
     }
 
@@ -98,16 +182,216 @@ impl AIProvider for CLIProxyAPIProvider {
         "cliproxyapi"
     }
 
+    fn configured_model(&self) -> Option<String> {
+        self.model.clone()
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
     async fn is_available(&self) -> bool {
         // Check if CLIProxyAPI server is reachable via models endpoint (OpenAI-compatible)
-        let models_url = format!("{}/v1/models", self.base_url.trim_end_matches('/'));
-
         self.client
-            .get(&models_url)
+            .get(self.models_url())
             .timeout(Duration::from_secs(3))
             .send()
             .await
             .map(|r| r.status().is_success())
             .unwrap_or(false)
     }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        self.models.get_or_fetch(self.fetch_models()).await
+    }
+
+    async fn diagnose(&self) -> ProviderDiagnostics {
+        let started = Instant::now();
+        let result = self.fetch_models().await;
+        if let Ok(models) = &result {
+            self.models.set(models.clone());
+        }
+        let no_accounts = matches!(&result, Ok(models) if models.is_empty());
+
+        let mut diagnostics =
+            ProviderDiagnostics::from_models(self.name(), self.model.as_deref(), result, started);
+        // The server lists models only for logged-in accounts
+        if no_accounts {
+            diagnostics.auth_ok = Some(false);
+            diagnostics.error = Some("No accounts logged in to CLIProxyAPI".to_string());
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{
+        anthropic_message, gemini_generate, models_list, openai_completion, user_message,
+        StubResponse, StubServer,
+    };
+    use serde_json::json;
+
+    const COMPLETIONS: &str = "/v1/chat/completions";
+    const MODELS: &str = "/v1/models";
+
+    fn provider(server: &StubServer, model: Option<&str>) -> CLIProxyAPIProvider {
+        CLIProxyAPIProvider::new(Some(server.url().to_string()), model.map(str::to_string))
+    }
+
+    #[tokio::test]
+    async fn completes_against_openai_compatible_endpoint() {
+        let server = StubServer::start().await;
+        server.on("POST", COMPLETIONS, StubResponse::json(200, openai_completion("C major", "claude-sonnet-4")));
+
+        let response = provider(&server, Some("claude-sonnet-4"))
+            .complete(user_message("pick a key"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "C major");
+        assert_eq!(response.provider, "cliproxyapi:claude-sonnet-4");
+        assert_eq!(response.tokens, Some(20));
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 8));
+
+        let body = server.requests_to(COMPLETIONS)[0].json();
+        assert_eq!(body["model"], "claude-sonnet-4");
+        assert_eq!(body["messages"][0]["content"], "pick a key");
+        assert_eq!(body["max_tokens"], 4096);
+    }
+
+    #[tokio::test]
+    async fn uses_first_discovered_model_when_none_configured() {
+        let server = StubServer::start().await;
+        server.on("GET", MODELS, StubResponse::json(200, models_list(&["gemini-2.0-flash", "gpt-4o"])));
+        server.on("POST", COMPLETIONS, StubResponse::json(200, openai_completion("ok", "gemini-2.0-flash")));
+
+        let provider = provider(&server, None);
+        provider.complete(user_message("hi")).await.unwrap();
+        provider.complete(user_message("hi")).await.unwrap();
+
+        assert_eq!(server.requests_to(COMPLETIONS)[0].json()["model"], "gemini-2.0-flash");
+        assert_eq!(server.requests_to(MODELS).len(), 1, "model list is cached");
+    }
+
+    #[tokio::test]
+    async fn rate_limit_errors_carry_retry_after() {
+        let server = StubServer::start().await;
+        server.on(
+            "POST",
+            COMPLETIONS,
+            StubResponse::json(429, json!({ "error": { "message": "slow down" } })).header("Retry-After", "2"),
+        );
+
+        let error = provider(&server, Some("m")).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Http { status: 429, .. }));
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(2)));
+        assert!(error.to_string().contains("slow down"));
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retryable() {
+        let server = StubServer::start().await;
+        server.on("POST", COMPLETIONS, StubResponse::raw(400, "bad request"));
+
+        let error = provider(&server, Some("m")).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Http { status: 400, .. }));
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_json() {
+        let server = StubServer::start().await;
+        server.on("POST", COMPLETIONS, StubResponse::raw(200, "{\"choices\": [oops"));
+
+        let error = provider(&server, Some("m")).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Network(_)));
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn rejects_non_openai_shaped_bodies() {
+        let server = StubServer::start().await;
+        server.on("POST", COMPLETIONS, StubResponse::json(200, anthropic_message("hi", "claude")));
+        let anthropic = provider(&server, Some("m")).complete(user_message("hi")).await;
+        assert!(anthropic.is_err());
+
+        let server = StubServer::start().await;
+        server.on("POST", COMPLETIONS, StubResponse::json(200, gemini_generate("hi")));
+        let gemini = provider(&server, Some("m")).complete(user_message("hi")).await;
+        assert!(gemini.is_err());
+    }
+
+    #[tokio::test]
+    async fn empty_choices_is_an_api_error() {
+        let server = StubServer::start().await;
+        server.on("POST", COMPLETIONS, StubResponse::json(200, json!({ "choices": [] })));
+
+        let error = provider(&server, Some("m")).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::ApiError(_)));
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_retryable() {
+        let server = StubServer::start().await;
+        server.on(
+            "POST",
+            COMPLETIONS,
+            StubResponse::json(200, openai_completion("late", "m")).delayed(Duration::from_secs(2)),
+        );
+
+        let mut provider = provider(&server, Some("m"));
+        provider.client = Client::builder().timeout(Duration::from_millis(200)).build().unwrap();
+        let error = provider.complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Network(_)));
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn availability_follows_models_endpoint() {
+        let server = StubServer::start().await;
+        server.on("GET", MODELS, StubResponse::json(200, models_list(&["m"])));
+        assert!(provider(&server, None).is_available().await);
+
+        let down = StubServer::start().await;
+        down.on("GET", MODELS, StubResponse::raw(503, "starting"));
+        assert!(!provider(&down, None).is_available().await);
+    }
+
+    #[tokio::test]
+    async fn diagnose_flags_missing_model_and_accounts() {
+        let server = StubServer::start().await;
+        server.on("GET", MODELS, StubResponse::json(200, models_list(&["gemini-2.5-pro"])));
+
+        let report = provider(&server, Some("claude-sonnet-4")).diagnose().await;
+        assert_eq!(report.model_available, Some(false));
+        assert!(report.error.unwrap().contains("claude-sonnet-4"));
+
+        let empty = StubServer::start().await;
+        empty.on("GET", MODELS, StubResponse::json(200, models_list(&[])));
+
+        let report = provider(&empty, None).diagnose().await;
+        assert!(report.reachable);
+        assert_eq!(report.auth_ok, Some(false));
+    }
+
+    #[tokio::test]
+    async fn diagnose_reports_unreachable_server() {
+        // Nothing listens on the discard port
+        let report = CLIProxyAPIProvider::new(Some("http://127.0.0.1:9".to_string()), None)
+            .diagnose()
+            .await;
+
+        assert!(!report.reachable);
+        assert!(report.latency_ms.is_none());
+        assert!(report.error.is_some());
+    }
 }
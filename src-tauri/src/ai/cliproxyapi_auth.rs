@@ -0,0 +1,238 @@
+use reqwest::{Client, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::cliproxyapi::ServerUrl;
+
+/// Header carrying the management secret
+const MANAGEMENT_KEY_HEADER: &str = "X-Management-Key";
+
+/// Account types CLIProxyAPI can log in through OAuth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginProvider {
+    Claude,
+    Gemini,
+    Codex,
+    Antigravity,
+}
+
+impl LoginProvider {
+    /// Management endpoint returning the provider's OAuth URL
+    fn auth_url_path(self) -> &'static str {
+        match self {
+            Self::Claude => "/v0/management/anthropic-auth-url?is_webui=true",
+            Self::Gemini => "/v0/management/gemini-cli-auth-url?is_webui=true",
+            Self::Codex => "/v0/management/codex-auth-url?is_webui=true",
+            Self::Antigravity => "/v0/management/antigravity-auth-url?is_webui=true",
+        }
+    }
+}
+
+/// A started OAuth login: open `url` in the browser, then poll with `state`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginStart {
+    pub url: String,
+    pub state: String,
+}
+
+/// Progress of an OAuth login
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginStatus {
+    /// "wait" while the user hasn't finished, then "ok" or "error"
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// An account logged in to CLIProxyAPI (one credential file in its auth dir)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthAccount {
+    #[serde(default)]
+    pub id: String,
+    /// Credential file name, used to log the account out
+    pub name: String,
+    #[serde(default)]
+    pub provider: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Deserialize)]
+struct AuthFilesResponse {
+    #[serde(default)]
+    files: Vec<AuthAccount>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Client for CLIProxyAPI's management API (OAuth logins and accounts)
+pub struct ManagementClient {
+    client: Client,
+    base_url: ServerUrl,
+    secret: String,
+}
+
+impl ManagementClient {
+    pub fn new(base_url: ServerUrl, secret: String) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_else(|_| Client::new()),
+            base_url,
+            secret,
+        }
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.request(reqwest::Method::GET, path)
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        let url = format!("{}{}", self.base_url.get().trim_end_matches('/'), path);
+        self.client
+            .request(method, url)
+            .header(MANAGEMENT_KEY_HEADER, &self.secret)
+    }
+
+    /// Start an OAuth login for `provider`
+    pub async fn start_login(&self, provider: LoginProvider) -> Result<LoginStart, String> {
+        let response = send(self.get(provider.auth_url_path()), "start login").await?;
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid login response: {}", e))
+    }
+
+    /// Check whether the login identified by `state` has finished
+    pub async fn login_status(&self, state: &str) -> Result<LoginStatus, String> {
+        let request = self
+            .get("/v0/management/get-auth-status")
+            .query(&[("state", state)]);
+        let response = send(request, "check login status").await?;
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid login status: {}", e))
+    }
+
+    /// Accounts logged in to the server
+    pub async fn list_accounts(&self) -> Result<Vec<AuthAccount>, String> {
+        let response = send(self.get("/v0/management/auth-files"), "list accounts").await?;
+        let body: AuthFilesResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid account list: {}", e))?;
+        Ok(body.files)
+    }
+
+    /// Log an account out by deleting its credential file
+    pub async fn logout(&self, name: &str) -> Result<(), String> {
+        let request = self
+            .request(reqwest::Method::DELETE, "/v0/management/auth-files")
+            .query(&[("name", name)]);
+        send(request, "log out").await.map(|_| ())
+    }
+}
+
+/// Send a management request, turning error statuses into readable messages
+async fn send(request: RequestBuilder, action: &str) -> Result<Response, String> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to {}: CLIProxyAPI unreachable ({})", action, e))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let detail = response
+        .json::<ErrorResponse>()
+        .await
+        .map(|body| body.error)
+        .unwrap_or_else(|_| status.to_string());
+    Err(match status.as_u16() {
+        401 | 403 => format!("Failed to {}: management secret rejected ({})", action, detail),
+        404 => format!("Failed to {}: management API not enabled ({})", action, detail),
+        _ => format!("Failed to {}: {}", action, detail),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{StubResponse, StubServer};
+    use serde_json::json;
+
+    fn client(server: &StubServer) -> ManagementClient {
+        ManagementClient::new(ServerUrl::new(server.url().to_string()), "s3cret".to_string())
+    }
+
+    #[tokio::test]
+    async fn runs_a_login_and_manages_accounts() {
+        let server = StubServer::start().await;
+        server.on(
+            "GET",
+            "/v0/management/anthropic-auth-url?is_webui=true",
+            StubResponse::json(200, json!({ "status": "ok", "url": "https://claude.ai/oauth", "state": "st-1" })),
+        );
+        server
+            .on("GET", "/v0/management/get-auth-status?state=st-1", StubResponse::json(200, json!({ "status": "wait" })))
+            .on("GET", "/v0/management/get-auth-status?state=st-1", StubResponse::json(200, json!({ "status": "ok" })));
+        server.on(
+            "GET",
+            "/v0/management/auth-files",
+            StubResponse::json(200, json!({ "files": [
+                { "id": "claude-a.json", "name": "claude-a.json", "provider": "claude", "label": "a", "status": "active", "email": "a@example.com" }
+            ]})),
+        );
+        server.on("DELETE", "/v0/management/auth-files?name=claude-a.json", StubResponse::json(200, json!({ "status": "ok" })));
+        let client = client(&server);
+
+        let login = client.start_login(LoginProvider::Claude).await.unwrap();
+        assert_eq!(login.url, "https://claude.ai/oauth");
+        assert_eq!(client.login_status(&login.state).await.unwrap().status, "wait");
+        assert_eq!(client.login_status(&login.state).await.unwrap().status, "ok");
+
+        let accounts = client.list_accounts().await.unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].email.as_deref(), Some("a@example.com"));
+        assert!(!accounts[0].disabled);
+
+        client.logout(&accounts[0].name).await.unwrap();
+        assert_eq!(server.requests_to("/v0/management/auth-files?name=claude-a.json")[0].method, "DELETE");
+        assert!(server
+            .requests()
+            .iter()
+            .all(|r| r.headers.get("x-management-key").map(String::as_str) == Some("s3cret")));
+    }
+
+    #[tokio::test]
+    async fn explains_management_errors() {
+        let server = StubServer::start().await;
+        server.on(
+            "GET",
+            "/v0/management/codex-auth-url?is_webui=true",
+            StubResponse::json(401, json!({ "error": "invalid management key" })),
+        );
+        let client = client(&server);
+
+        let error = client.start_login(LoginProvider::Codex).await.unwrap_err();
+        assert!(error.contains("management secret rejected"), "{}", error);
+        assert!(error.contains("invalid management key"), "{}", error);
+
+        let error = client.list_accounts().await.unwrap_err();
+        assert!(error.contains("management API not enabled"), "{}", error);
+    }
+}
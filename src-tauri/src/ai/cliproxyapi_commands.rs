@@ -1,8 +1,12 @@
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use super::cliproxyapi_manager::CLIProxyAPIManager;
+use super::cliproxyapi_auth::{AuthAccount, LoginProvider, LoginStart, LoginStatus, ManagementClient};
+use super::cliproxyapi_config::CLIProxyAPIConfig;
+use super::cliproxyapi_log::LogLine;
+use super::cliproxyapi_manager::{CLIProxyAPIManager, DownloadCancel, DownloadProgress, ServerState};
 
 /// Tauri command to check if CLIProxyAPI is installed
 #[tauri::command]
@@ -14,12 +18,42 @@ pub async fn cliproxyapi_is_installed(
 }
 
 /// Tauri command to download/install CLIProxyAPI
+///
+/// Progress is emitted as `cliproxyapi-download-progress` events.
 #[tauri::command]
 pub async fn cliproxyapi_download(
+    app: AppHandle,
     state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
 ) -> Result<String, String> {
-    let manager = state.lock().await;
-    manager.download().await
+    let on_progress = |progress: DownloadProgress| {
+        if let Err(e) = app.emit("cliproxyapi-download-progress", progress) {
+            eprintln!("[CLIProxyAPI] Failed to emit download progress: {}", e);
+        }
+    };
+
+    CLIProxyAPIManager::download(&state, &on_progress).await
+}
+
+/// Tauri command to cancel a running CLIProxyAPI download
+#[tauri::command]
+pub async fn cliproxyapi_cancel_download(cancel: State<'_, DownloadCancel>) -> Result<(), String> {
+    cancel.cancel();
+    Ok(())
+}
+
+/// Tauri command to install CLIProxyAPI from a local .tar.gz/.zip archive (offline)
+///
+/// Returns the installed version, if the binary reports one.
+#[tauri::command]
+pub async fn cliproxyapi_install_from_archive(
+    path: String,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<Option<String>, String> {
+    CLIProxyAPIManager::blocking(&state, move |m| {
+        m.install_from_archive(Path::new(&path))?;
+        Ok(m.get_installed_version())
+    })
+    .await
 }
 
 /// Tauri command to start CLIProxyAPI server
@@ -27,8 +61,7 @@ pub async fn cliproxyapi_download(
 pub async fn cliproxyapi_start(
     state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
 ) -> Result<(), String> {
-    let manager = state.lock().await;
-    manager.start()
+    CLIProxyAPIManager::blocking(&state, |manager| manager.start()).await
 }
 
 /// Tauri command to stop CLIProxyAPI server
@@ -36,8 +69,40 @@ pub async fn cliproxyapi_start(
 pub async fn cliproxyapi_stop(
     state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
 ) -> Result<(), String> {
+    CLIProxyAPIManager::blocking(&state, |manager| manager.stop()).await
+}
+
+/// Tauri command to get recent CLIProxyAPI output (default: last 200 lines)
+#[tauri::command]
+pub async fn cliproxyapi_tail_logs(
+    lines: Option<usize>,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<Vec<LogLine>, String> {
     let manager = state.lock().await;
-    manager.stop()
+    Ok(manager.log().tail(lines.unwrap_or(200)))
+}
+
+/// Tauri command to start or stop streaming CLIProxyAPI output
+///
+/// While enabled, each new line is emitted as a `cliproxyapi-log` event.
+#[tauri::command]
+pub async fn cliproxyapi_stream_logs(
+    enabled: bool,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<(), String> {
+    let manager = state.lock().await;
+    if !enabled {
+        manager.log().set_listener(None);
+        return Ok(());
+    }
+
+    manager.log().set_listener(Some(Box::new(move |line: &LogLine| {
+        if let Err(e) = app.emit("cliproxyapi-log", line.clone()) {
+            eprintln!("[CLIProxyAPI] Failed to emit log event: {}", e);
+        }
+    })));
+    Ok(())
 }
 
 /// Tauri command to check if CLIProxyAPI server is running
@@ -49,6 +114,15 @@ pub async fn cliproxyapi_is_running(
     Ok(manager.is_running())
 }
 
+/// Tauri command to get the server state (e.g. whether another process holds the port)
+#[tauri::command]
+pub async fn cliproxyapi_status(
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<ServerState, String> {
+    let manager = state.lock().await;
+    Ok(manager.server_state())
+}
+
 /// Tauri command to get CLIProxyAPI server URL
 #[tauri::command]
 pub async fn cliproxyapi_get_url(
@@ -67,18 +141,90 @@ pub async fn cliproxyapi_get_version(
     Ok(manager.get_installed_version())
 }
 
+/// Tauri command to read the editable settings from config.yaml
+#[tauri::command]
+pub async fn cliproxyapi_get_config(
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<CLIProxyAPIConfig, String> {
+    let manager = state.lock().await;
+    manager.config()
+}
+
+/// Tauri command to validate and save settings, restarting the server if it's running
+#[tauri::command]
+pub async fn cliproxyapi_set_config(
+    config: CLIProxyAPIConfig,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<(), String> {
+    CLIProxyAPIManager::apply_config(&state, config).await
+}
+
 /// Tauri command to check for updates (returns latest version if newer)
 #[tauri::command]
 pub async fn cliproxyapi_check_update(
     state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
 ) -> Result<Option<String>, String> {
     let manager = state.lock().await;
-    let (latest, _) = manager.get_latest_release().await?;
+    manager.check_update().await
+}
 
-    if let Some(installed) = manager.get_installed_version() {
-        if installed != latest {
-            return Ok(Some(latest));
+/// Tauri command to update CLIProxyAPI to the latest release
+///
+/// Rolls back to the previous binary if the new one doesn't come up.
+/// Returns the new version, or `None` if already up to date. Download
+/// progress is emitted as `cliproxyapi-download-progress` events.
+#[tauri::command]
+pub async fn cliproxyapi_update(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<Option<String>, String> {
+    let on_progress = |progress: DownloadProgress| {
+        if let Err(e) = app.emit("cliproxyapi-download-progress", progress) {
+            eprintln!("[CLIProxyAPI] Failed to emit download progress: {}", e);
         }
-    }
-    Ok(None)
+    };
+
+    CLIProxyAPIManager::update(&state, &on_progress).await
+}
+
+/// Management API client for the managed server (released before any request is sent)
+async fn management_client(
+    state: &State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<ManagementClient, String> {
+    state.lock().await.management_client()
+}
+
+/// Tauri command to start an OAuth login; open the returned URL, then poll `cliproxyapi_login_status`
+#[tauri::command]
+pub async fn cliproxyapi_start_login(
+    provider: LoginProvider,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<LoginStart, String> {
+    management_client(&state).await?.start_login(provider).await
+}
+
+/// Tauri command to check an OAuth login started with `cliproxyapi_start_login`
+#[tauri::command]
+pub async fn cliproxyapi_login_status(
+    login_state: String,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<LoginStatus, String> {
+    management_client(&state).await?.login_status(&login_state).await
+}
+
+/// Tauri command to list the accounts logged in to CLIProxyAPI
+#[tauri::command]
+pub async fn cliproxyapi_list_accounts(
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<Vec<AuthAccount>, String> {
+    management_client(&state).await?.list_accounts().await
+}
+
+/// Tauri command to log an account out
+#[tauri::command]
+pub async fn cliproxyapi_logout(
+    name: String,
+    state: State<'_, Arc<Mutex<CLIProxyAPIManager>>>,
+) -> Result<(), String> {
+    management_client(&state).await?.logout(&name).await
 }
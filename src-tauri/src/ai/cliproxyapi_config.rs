@@ -0,0 +1,291 @@
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use super::cliproxyapi::DEFAULT_PORT;
+use super::storage::write_private_atomic;
+
+/// Log levels accepted in `log-level`
+pub const LOG_LEVELS: [&str; 4] = ["debug", "info", "warn", "error"];
+
+/// Length of generated management secrets
+const SECRET_LEN: usize = 32;
+
+const HEADER: &str = "# CLIProxyAPI Configuration for OpenMusic\n";
+
+/// Settings of the management API (used for OAuth logins)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManagementSettings {
+    /// Accept management requests from other machines
+    pub allow_remote: bool,
+    /// Don't serve the bundled web control panel
+    pub disable_control_panel: bool,
+}
+
+/// The parts of CLIProxyAPI's config.yaml that OpenMusic edits
+///
+/// Other keys in the file are left untouched. The management secret is
+/// kept out of this struct; see `set_management_secret`.
+///
+/// Edits go through `serde_yaml`, which drops comments and formatting, so
+/// the file is only rewritten when one of the edited values changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CLIProxyAPIConfig {
+    /// Address to bind (e.g. "127.0.0.1")
+    pub host: String,
+    pub port: u16,
+    /// One of `LOG_LEVELS`
+    pub log_level: String,
+    /// Directory holding OAuth credentials ("~" is expanded)
+    pub auth_dir: String,
+    pub management: ManagementSettings,
+}
+
+impl Default for CLIProxyAPIConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: DEFAULT_PORT,
+            log_level: "info".to_string(),
+            auth_dir: "~/.openmusic-cliproxyapi".to_string(),
+            management: ManagementSettings {
+                allow_remote: false,
+                disable_control_panel: false,
+            },
+        }
+    }
+}
+
+impl CLIProxyAPIConfig {
+    /// Read the config file; missing keys (or a missing file) take defaults
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let yaml = read_mapping(path)?;
+        let defaults = Self::default();
+        let management = yaml.get("remote-management").and_then(Value::as_mapping);
+        let flag = |key: &str, default: bool| {
+            management
+                .and_then(|m| m.get(key))
+                .and_then(Value::as_bool)
+                .unwrap_or(default)
+        };
+
+        Ok(Self {
+            host: string(&yaml, "host").unwrap_or(defaults.host),
+            port: yaml
+                .get("port")
+                .and_then(Value::as_u64)
+                .and_then(|port| u16::try_from(port).ok())
+                .unwrap_or(defaults.port),
+            log_level: string(&yaml, "log-level").unwrap_or(defaults.log_level),
+            auth_dir: string(&yaml, "auth-dir").unwrap_or(defaults.auth_dir),
+            management: ManagementSettings {
+                allow_remote: flag("allow-remote", defaults.management.allow_remote),
+                disable_control_panel: flag(
+                    "disable-control-panel",
+                    defaults.management.disable_control_panel,
+                ),
+            },
+        })
+    }
+
+    /// Write these settings into the config file, keeping any other keys
+    ///
+    /// Does nothing if the file already holds these values.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        update_mapping(path, |yaml| {
+            yaml.insert("host".into(), self.host.clone().into());
+            yaml.insert("port".into(), self.port.into());
+            yaml.insert("log-level".into(), self.log_level.clone().into());
+            yaml.insert("auth-dir".into(), self.auth_dir.clone().into());
+
+            let management = management_mapping(yaml);
+            management.insert("allow-remote".into(), self.management.allow_remote.into());
+            management.insert(
+                "disable-control-panel".into(),
+                self.management.disable_control_panel.into(),
+            );
+        })
+    }
+
+    /// Check the settings before they are written and the server restarted
+    ///
+    /// All problems are reported at once, separated by "; ".
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+
+        if self.host != "localhost" && self.host.parse::<IpAddr>().is_err() {
+            problems.push(format!("host must be an IP address or \"localhost\", got \"{}\"", self.host));
+        }
+        if self.port == 0 {
+            problems.push("port must be between 1 and 65535".to_string());
+        }
+        if !LOG_LEVELS.contains(&self.log_level.as_str()) {
+            problems.push(format!(
+                "log level must be one of {}, got \"{}\"",
+                LOG_LEVELS.join(", "),
+                self.log_level
+            ));
+        }
+        if self.auth_dir.trim().is_empty() {
+            problems.push("auth dir must not be empty".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid CLIProxyAPI config: {}", problems.join("; ")))
+        }
+    }
+
+    /// `auth_dir` with a leading "~" replaced by the home directory
+    pub fn auth_dir_path(&self) -> PathBuf {
+        match (self.auth_dir.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ if self.auth_dir == "~" => dirs::home_dir().unwrap_or_else(|| PathBuf::from("~")),
+            _ => PathBuf::from(&self.auth_dir),
+        }
+    }
+}
+
+/// Set `remote-management.secret-key` in the config file
+pub fn set_management_secret(path: &Path, secret: &str) -> Result<(), String> {
+    update_mapping(path, |yaml| {
+        management_mapping(yaml).insert("secret-key".into(), secret.into());
+    })
+}
+
+/// Apply `edit` to the config file, rewriting it only if a value changed
+fn update_mapping(path: &Path, edit: impl FnOnce(&mut Mapping)) -> Result<(), String> {
+    let original = read_mapping(path)?;
+    let mut yaml = original.clone();
+    edit(&mut yaml);
+
+    if yaml == original {
+        return Ok(());
+    }
+    write_mapping(path, &yaml)
+}
+
+/// Random secret for the management API
+pub fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LEN)
+        .map(char::from)
+        .collect()
+}
+
+fn string(yaml: &Mapping, key: &str) -> Option<String> {
+    yaml.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/// The `remote-management` section, created if missing
+fn management_mapping(yaml: &mut Mapping) -> &mut Mapping {
+    let entry = yaml
+        .entry("remote-management".into())
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    if !entry.is_mapping() {
+        *entry = Value::Mapping(Mapping::new());
+    }
+    entry.as_mapping_mut().unwrap()
+}
+
+fn read_mapping(path: &Path) -> Result<Mapping, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Mapping::new()),
+        Err(e) => return Err(format!("Failed to read config: {}", e)),
+    };
+
+    match serde_yaml::from_str(&text).map_err(|e| format!("Invalid config.yaml: {}", e))? {
+        Value::Null => Ok(Mapping::new()),
+        Value::Mapping(mapping) => Ok(mapping),
+        _ => Err("Invalid config.yaml: expected a mapping at the top level".to_string()),
+    }
+}
+
+fn write_mapping(path: &Path, yaml: &Mapping) -> Result<(), String> {
+    let text = serde_yaml::to_string(yaml).map_err(|e| format!("Failed to encode config: {}", e))?;
+    // Holds the management secret until CLIProxyAPI replaces it with a hash
+    write_private_atomic(path, format!("{}{}", HEADER, text).as_bytes())
+        .map_err(|e| format!("Failed to write config: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::TempDir;
+
+    fn temp_config(contents: &str) -> (PathBuf, TempDir) {
+        let dir = TempDir::new("cliproxyapi-config");
+        let path = dir.join("config.yaml");
+        std::fs::write(&path, contents).unwrap();
+        (path, dir)
+    }
+
+    #[test]
+    fn edits_known_keys_and_keeps_the_rest() {
+        let (path, _dir) = temp_config(
+            "port: 9000\nproxy-url: socks5://127.0.0.1:1080\nremote-management:\n  allow-remote: true\n  secret-key: $2a$10$hash\n",
+        );
+
+        let mut config = CLIProxyAPIConfig::load(&path).unwrap();
+        assert_eq!(config.port, 9000);
+        assert!(config.management.allow_remote);
+        assert_eq!(config.host, "127.0.0.1");
+
+        config.log_level = "debug".to_string();
+        config.management.allow_remote = false;
+        config.save(&path).unwrap();
+
+        assert_eq!(CLIProxyAPIConfig::load(&path).unwrap(), config);
+        let yaml = read_mapping(&path).unwrap();
+        assert_eq!(string(&yaml, "proxy-url").as_deref(), Some("socks5://127.0.0.1:1080"));
+        assert_eq!(
+            yaml["remote-management"]["secret-key"].as_str(),
+            Some("$2a$10$hash")
+        );
+    }
+
+    #[test]
+    fn unchanged_values_leave_the_file_alone() {
+        let contents = "# tuned by hand\nport: 8317\nhost: 127.0.0.1\nlog-level: info\nauth-dir: ~/.auth\n\
+                        remote-management:\n  allow-remote: false  # keep local\n  disable-control-panel: false\n  secret-key: abc\n";
+        let (path, _dir) = temp_config(contents);
+
+        CLIProxyAPIConfig::load(&path).unwrap().save(&path).unwrap();
+        set_management_secret(&path, "abc").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+
+        set_management_secret(&path, "new").unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("tuned by hand"));
+    }
+
+    #[test]
+    fn reports_every_invalid_setting() {
+        let config = CLIProxyAPIConfig {
+            host: "not a host".to_string(),
+            port: 0,
+            log_level: "verbose".to_string(),
+            auth_dir: " ".to_string(),
+            ..CLIProxyAPIConfig::default()
+        };
+
+        let error = config.validate().unwrap_err();
+        for field in ["host", "port", "log level", "auth dir"] {
+            assert!(error.contains(field), "{}", error);
+        }
+        assert!(CLIProxyAPIConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn generates_distinct_secrets() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), SECRET_LEN);
+        assert!(secret.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(secret, generate_secret());
+    }
+}
@@ -0,0 +1,186 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::storage::now_millis;
+
+/// Lines kept in memory for `tail`
+const BUFFER_LINES: usize = 1000;
+
+/// Log file size that triggers rotation (the previous file is kept as `.1`)
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// One line of CLIProxyAPI output
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    /// Unix millis when the line was read
+    pub timestamp: u64,
+    /// "stdout" or "stderr"
+    pub stream: String,
+    pub line: String,
+}
+
+/// Callback receiving each new line (e.g. to emit Tauri events)
+pub type LogListener = Box<dyn Fn(&LogLine) + Send + Sync>;
+
+struct LogFile {
+    file: Option<File>,
+    size: u64,
+}
+
+/// Output of the CLIProxyAPI process: rotated log file plus in-memory ring buffer
+pub struct ProcessLog {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Mutex<LogFile>,
+    buffer: Mutex<VecDeque<LogLine>>,
+    listener: Mutex<Option<LogListener>>,
+}
+
+impl ProcessLog {
+    /// Create a log writing to `path` (opened lazily, appended to)
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            max_bytes: MAX_LOG_BYTES,
+            file: Mutex::new(LogFile { file: None, size: 0 }),
+            buffer: Mutex::new(VecDeque::with_capacity(BUFFER_LINES)),
+            listener: Mutex::new(None),
+        }
+    }
+
+    /// Path of the current log file
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Forward new lines to `listener` (replacing any previous one), or stop with `None`
+    pub fn set_listener(&self, listener: Option<LogListener>) {
+        *self.listener.lock().unwrap() = listener;
+    }
+
+    /// Most recent `lines` lines, oldest first
+    pub fn tail(&self, lines: usize) -> Vec<LogLine> {
+        let buffer = self.buffer.lock().unwrap();
+        buffer.iter().skip(buffer.len().saturating_sub(lines)).cloned().collect()
+    }
+
+    /// Record a line of output
+    pub fn append(&self, stream: &str, line: &str) {
+        let entry = LogLine {
+            timestamp: now_millis(),
+            stream: stream.to_string(),
+            line: line.trim_end_matches(['\r', '\n']).to_string(),
+        };
+
+        if let Err(e) = self.write(&entry) {
+            eprintln!("[CLIProxyAPI] Failed to write log: {}", e);
+        }
+
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            if buffer.len() == BUFFER_LINES {
+                buffer.pop_front();
+            }
+            buffer.push_back(entry.clone());
+        }
+
+        if let Some(listener) = self.listener.lock().unwrap().as_ref() {
+            listener(&entry);
+        }
+    }
+
+    /// Read `reader` line by line until it closes (run on a dedicated thread)
+    ///
+    /// Invalid UTF-8 is replaced rather than ending the capture.
+    pub fn capture(&self, stream: &str, reader: impl Read) {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => self.append(stream, &String::from_utf8_lossy(&line)),
+            }
+        }
+    }
+
+    /// Append a line to the log file, rotating it when it grows too large
+    fn write(&self, entry: &LogLine) -> std::io::Result<()> {
+        let mut log = self.file.lock().unwrap();
+
+        if log.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            log.size = file.metadata()?.len();
+            log.file = Some(file);
+        }
+
+        if log.size >= self.max_bytes {
+            log.file = None;
+            let mut rotated = self.path.as_os_str().to_owned();
+            rotated.push(".1");
+            std::fs::rename(&self.path, rotated)?;
+            log.file = Some(File::create(&self.path)?);
+            log.size = 0;
+        }
+
+        let text = format!("{} [{}] {}\n", entry.timestamp, entry.stream, entry.line);
+        if let Some(file) = log.file.as_mut() {
+            file.write_all(text.as_bytes())?;
+        }
+        log.size += text.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::TempDir;
+    use std::sync::Arc;
+
+    fn temp_log() -> (ProcessLog, TempDir) {
+        let dir = TempDir::new("log");
+        (ProcessLog::new(dir.join("cliproxyapi.log")), dir)
+    }
+
+    #[test]
+    fn captures_lines_into_file_and_ring_buffer() {
+        let (log, _dir) = temp_log();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        log.set_listener(Some(Box::new(move |line: &LogLine| sink.lock().unwrap().push(line.line.clone()))));
+
+        log.capture("stderr", &b"auth failed\r\n\xffbad utf8\nno newline"[..]);
+
+        let lines: Vec<String> = log.tail(10).into_iter().map(|l| l.line).collect();
+        assert_eq!(lines, ["auth failed", "\u{fffd}bad utf8", "no newline"]);
+        assert_eq!(log.tail(1)[0].stream, "stderr");
+        assert_eq!(*seen.lock().unwrap(), lines);
+
+        let file = std::fs::read_to_string(log.path()).unwrap();
+        assert!(file.contains("[stderr] auth failed\n"));
+    }
+
+    #[test]
+    fn rotates_and_bounds_memory() {
+        let (mut log, dir) = temp_log();
+        log.max_bytes = 200;
+
+        for i in 0..(BUFFER_LINES + 5) {
+            log.append("stdout", &format!("line {}", i));
+        }
+
+        assert_eq!(log.tail(usize::MAX).len(), BUFFER_LINES);
+        assert_eq!(log.tail(1)[0].line, format!("line {}", BUFFER_LINES + 4));
+        assert!(std::fs::metadata(log.path()).unwrap().len() <= 200 + 64);
+        assert!(dir.join("cliproxyapi.log.1").exists());
+    }
+}
@@ -1,10 +1,126 @@
-use std::io::{Cursor, Read};
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::fs;
+use tokio::sync::OwnedMutexGuard;
+use tokio::io::AsyncWriteExt;
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::cliproxyapi::{ServerUrl, DEFAULT_PORT};
+use super::cliproxyapi_auth::ManagementClient;
+use super::cliproxyapi_config::{generate_secret, set_management_secret, CLIProxyAPIConfig};
+use super::cliproxyapi_log::ProcessLog;
+use super::storage::write_private_atomic;
+
+/// GitHub API URL of the CLIProxyAPI repository
+///
+/// Override with `CLIPROXYAPI_RELEASE_API` or `set_release_api_url` (e.g. for a mirror).
+const DEFAULT_RELEASE_API: &str = "https://api.github.com/repos/router-for-me/CLIProxyAPI";
+
+/// How long a stopping server gets to exit before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Minimum time between download progress reports
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Download progress, reported while fetching a release archive
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    /// Bytes received so far
+    pub received: u64,
+    /// Archive size, if the server reported it
+    pub total: Option<u64>,
+    /// Average download speed
+    pub bytes_per_sec: u64,
+}
+
+/// Cancels an in-progress download
+///
+/// Cloned out of the manager so it can be triggered from another command
+/// while a download runs.
+#[derive(Clone, Default)]
+pub struct DownloadCancel(Arc<AtomicBool>);
+
+impl DownloadCancel {
+    /// Ask the running download to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// State of the CLIProxyAPI server, as seen by the manager and supervisor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerState {
+    Stopped,
+    /// Our process is alive but not accepting connections yet
+    Starting,
+    Running,
+    /// Our process is alive but `/v1/models` doesn't answer
+    Unhealthy,
+    /// The port is held by a process that isn't ours
+    PortInUse,
+    /// Restarted after a crash; waiting for it to come up
+    Restarting,
+    /// The supervisor gave up restarting
+    Failed,
+}
+
+/// Supervisor status, reported whenever it changes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SupervisorStatus {
+    pub state: ServerState,
+    /// Consecutive restarts since the server was last healthy
+    pub restarts: u32,
+    pub error: Option<String>,
+}
+
+/// Timing and limits for `CLIProxyAPIManager::supervise`
+#[derive(Debug, Clone)]
+pub struct SupervisorPolicy {
+    pub poll_interval: Duration,
+    /// Consecutive restarts before giving up
+    pub max_restarts: u32,
+    /// Delay before checking the first restart; doubles per attempt
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Failed health polls before a live but unresponsive process is restarted
+    pub unhealthy_polls: u32,
+}
+
+impl Default for SupervisorPolicy {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            max_restarts: 5,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            unhealthy_polls: 3,
+        }
+    }
+}
+
+impl SupervisorPolicy {
+    /// Wait after the given restart attempt (1-based)
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
 
 /// GitHub release asset info
 #[derive(Debug, Deserialize)]
@@ -17,6 +133,181 @@ struct GitHubRelease {
 struct GitHubAsset {
     name: String,
     browser_download_url: String,
+    /// Digest computed by GitHub (e.g. "sha256:ab12..."), on newer releases
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+/// Whether the server at `url` answers `/v1/models` successfully
+async fn server_healthy(client: &Client, url: &str) -> bool {
+    client
+        .get(format!("{}/v1/models", url))
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await
+        .is_ok_and(|r| r.status().is_success())
+}
+
+/// Where releases are fetched from and downloads are staged
+///
+/// Cloned out of the manager so downloads don't hold its lock.
+struct ReleaseSource {
+    api_url: String,
+    install_dir: PathBuf,
+    cancel: DownloadCancel,
+}
+
+impl ReleaseSource {
+    /// Fetch the latest release metadata
+    async fn fetch_latest_release(&self, client: &Client) -> Result<GitHubRelease, String> {
+        let url = format!("{}/releases/latest", self.api_url.trim_end_matches('/'));
+
+        let response = client
+            .get(&url)
+            .header("User-Agent", "OpenMusic")
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch releases: {}", e))?;
+
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse release: {}", e))
+    }
+
+    /// Get the expected SHA-256 of a release asset (lowercase hex)
+    ///
+    /// Uses a `<asset>.sha256` file, then a `checksums.txt` listing, then
+    /// the digest GitHub reports for the asset.
+    async fn fetch_checksum(
+        &self,
+        client: &Client,
+        release: &GitHubRelease,
+        asset: &GitHubAsset,
+    ) -> Result<String, String> {
+        let sidecar = format!("{}.sha256", asset.name);
+        let checksum_asset = release
+            .assets
+            .iter()
+            .find(|a| a.name == sidecar)
+            .or_else(|| release.assets.iter().find(|a| a.name.eq_ignore_ascii_case("checksums.txt")));
+
+        if let Some(checksum_asset) = checksum_asset {
+            let text = client
+                .get(&checksum_asset.browser_download_url)
+                .header("User-Agent", "OpenMusic")
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("Failed to download checksum: {}", e))?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read checksum: {}", e))?;
+
+            return parse_checksum(&text, &asset.name)
+                .ok_or_else(|| format!("No checksum for {} in {}", asset.name, checksum_asset.name));
+        }
+
+        asset
+            .digest
+            .as_deref()
+            .and_then(|d| d.strip_prefix("sha256:"))
+            .and_then(|d| parse_checksum(d, &asset.name))
+            .ok_or_else(|| format!("No checksum published for {}", asset.name))
+    }
+
+    /// Download the platform archive of a release and verify its SHA-256
+    ///
+    /// Returns the verified archive, staged in the install directory for
+    /// the caller to install and remove.
+    async fn fetch_archive(
+        &self,
+        client: &Client,
+        release: &GitHubRelease,
+        on_progress: &(dyn Fn(DownloadProgress) + Send + Sync),
+    ) -> Result<PathBuf, String> {
+        self.cancel.reset();
+
+        let asset = CLIProxyAPIManager::platform_asset(release)?;
+        let expected = self.fetch_checksum(client, release, asset).await?;
+
+        fs::create_dir_all(&self.install_dir)
+            .await
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+        // Keep the asset name last so the archive format can be told by extension
+        let archive_path = self.install_dir.join(format!(".download-{}", asset.name));
+        let result = self
+            .download_archive(client, asset, &archive_path, on_progress)
+            .await
+            .and_then(|digest| verify_checksum(&digest, &expected, &asset.name));
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&archive_path);
+            return Err(e);
+        }
+        Ok(archive_path)
+    }
+
+    /// Stream a release asset to `dest`, returning its SHA-256 (lowercase hex)
+    async fn download_archive(
+        &self,
+        client: &Client,
+        asset: &GitHubAsset,
+        dest: &Path,
+        on_progress: &(dyn Fn(DownloadProgress) + Send + Sync),
+    ) -> Result<String, String> {
+        let mut response = client
+            .get(&asset.browser_download_url)
+            .header("User-Agent", "OpenMusic")
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to download: {}", e))?;
+
+        let mut file = fs::File::create(dest)
+            .await
+            .map_err(|e| format!("Failed to create download file: {}", e))?;
+
+        let total = response.content_length();
+        let started = Instant::now();
+        let progress = |received: u64| DownloadProgress {
+            received,
+            total,
+            bytes_per_sec: (received as f64 / started.elapsed().as_secs_f64().max(0.001)) as u64,
+        };
+
+        let mut hasher = Sha256::new();
+        let mut received = 0u64;
+        let mut last_report: Option<Instant> = None;
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Failed to download: {}", e))?
+        {
+            hasher.update(&chunk);
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write download: {}", e))?;
+            received += chunk.len() as u64;
+
+            if last_report.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+                on_progress(progress(received));
+                last_report = Some(Instant::now());
+            }
+            if self.cancel.is_cancelled() {
+                return Err("Download cancelled".to_string());
+            }
+        }
+
+        file.flush()
+            .await
+            .map_err(|e| format!("Failed to write download: {}", e))?;
+        on_progress(progress(received));
+
+        Ok(hex(&hasher.finalize()))
+    }
 }
 
 /// CLIProxyAPI process manager - handles download, spawn, and lifecycle
@@ -24,25 +315,74 @@ pub struct CLIProxyAPIManager {
     process: Mutex<Option<Child>>,
     binary_path: PathBuf,
     config_path: PathBuf,
-    port: u16,
+    /// Plaintext management API secret (config.yaml only keeps its hash once the server ran)
+    secret_path: PathBuf,
+    /// Records the server PID so it can be found again after an app restart
+    pid_path: PathBuf,
+    /// Captured stdout/stderr of the server
+    log: Arc<ProcessLog>,
+    /// Port the server listens on; may move when the configured one is taken
+    port: AtomicU16,
+    /// URL shared with the provider, kept in sync with `port`
+    url: ServerUrl,
+    release_api_url: String,
+    cancel: DownloadCancel,
+    /// How long an updated server gets to answer before rolling back
+    health_timeout: Duration,
+    /// The server should be running (set by `start`, cleared by `stop`)
+    keep_alive: AtomicBool,
 }
 
 impl CLIProxyAPIManager {
     /// Create a new CLIProxyAPI manager
-    pub fn new(port: u16) -> Self {
+    ///
+    /// Uses the port from an existing config.yaml, or `DEFAULT_PORT`.
+    pub fn new() -> Self {
         let data_dir = dirs::data_local_dir()
             .unwrap_or_else(|| PathBuf::from("."))
             .join("openmusic")
             .join("cliproxyapi");
+        let port = CLIProxyAPIConfig::load(&data_dir.join("config.yaml"))
+            .map(|config| config.port)
+            .unwrap_or(DEFAULT_PORT);
 
+        Self::with_data_dir(data_dir, port)
+    }
+
+    /// Create a CLIProxyAPI manager that installs into `data_dir`, preferring `port`
+    pub fn with_data_dir(data_dir: PathBuf, port: u16) -> Self {
         Self {
             process: Mutex::new(None),
             binary_path: data_dir.join(Self::binary_name()),
             config_path: data_dir.join("config.yaml"),
-            port,
+            secret_path: data_dir.join("management.key"),
+            pid_path: data_dir.join("cliproxyapi.pid"),
+            log: Arc::new(ProcessLog::new(data_dir.join("cliproxyapi.log"))),
+            port: AtomicU16::new(port),
+            url: ServerUrl::new(format!("http://localhost:{}", port)),
+            release_api_url: std::env::var("CLIPROXYAPI_RELEASE_API")
+                .unwrap_or_else(|_| DEFAULT_RELEASE_API.to_string()),
+            cancel: DownloadCancel::default(),
+            health_timeout: Duration::from_secs(15),
+            keep_alive: AtomicBool::new(false),
         }
     }
 
+    /// Output captured from the server process
+    pub fn log(&self) -> &Arc<ProcessLog> {
+        &self.log
+    }
+
+    /// Handle for cancelling downloads started by this manager
+    pub fn download_cancel(&self) -> DownloadCancel {
+        self.cancel.clone()
+    }
+
+    /// Fetch releases from another GitHub-compatible API base URL
+    pub fn set_release_api_url(&mut self, url: String) {
+        self.release_api_url = url;
+    }
+
     /// Get platform-specific binary name
     fn binary_name() -> &'static str {
         #[cfg(target_os = "windows")]
@@ -76,158 +416,486 @@ impl CLIProxyAPIManager {
 
     /// Get the server URL
     pub fn get_url(&self) -> String {
-        format!("http://localhost:{}", self.port)
+        self.url.get()
     }
 
-    /// Get latest release info from GitHub
-    pub async fn get_latest_release(&self) -> Result<(String, String), String> {
-        let client = Client::new();
-        let url = "https://api.github.com/repos/router-for-me/CLIProxyAPI/releases/latest";
+    /// Shared handle to the server URL, updated when the server changes port
+    pub fn server_url(&self) -> ServerUrl {
+        self.url.clone()
+    }
 
-        let response = client
-            .get(url)
-            .header("User-Agent", "OpenMusic")
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch releases: {}", e))?;
+    /// Port the server listens on (or will, once started)
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
 
-        let release: GitHubRelease = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse release: {}", e))?;
+    fn set_port(&self, port: u16) {
+        self.port.store(port, Ordering::Relaxed);
+        self.url.set(format!("http://localhost:{}", port));
+    }
 
+    /// Make sure the server gets a free port and config.yaml names it
+    ///
+    /// Moves to an OS-assigned port when the current one is taken.
+    fn claim_port(&self) -> Result<(), String> {
+        let mut port = self.port();
+        if !port_available(port) {
+            let free = free_port()?;
+            eprintln!("[CLIProxyAPI] Port {} is in use, switching to {}", port, free);
+            port = free;
+        }
+
+        let mut config = CLIProxyAPIConfig::load(&self.config_path)?;
+        if config.port != port {
+            config.port = port;
+            config.save(&self.config_path)?;
+        }
+        self.set_port(port);
+        Ok(())
+    }
+
+    /// Current settings from config.yaml
+    pub fn config(&self) -> Result<CLIProxyAPIConfig, String> {
+        CLIProxyAPIConfig::load(&self.config_path)
+    }
+
+    /// Validate and save new settings, restarting a running server with them
+    ///
+    /// If the server doesn't come back healthy, the previous config.yaml is
+    /// restored and the server restarted with it.
+    pub async fn apply_config(
+        manager: &Arc<tokio::sync::Mutex<Self>>,
+        config: CLIProxyAPIConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+
+        let guard = manager.clone().lock_owned().await;
+        let previous = std::fs::read(&guard.config_path).ok();
+        let previous_port = guard.port();
+        let was_running = guard.keep_alive.load(Ordering::Relaxed);
+
+        config.save(&guard.config_path)?;
+        std::fs::create_dir_all(config.auth_dir_path())
+            .map_err(|e| format!("Failed to create auth dir: {}", e))?;
+        guard.set_port(config.port);
+
+        if !was_running {
+            return Ok(());
+        }
+
+        eprintln!("[CLIProxyAPI] Restarting with new config");
+        let (guard, restarted) = Self::blocking_with(guard, |m| m.stop().and_then(|_| m.start())).await;
+        let result = match restarted {
+            Ok(()) => guard.wait_until_healthy(&Client::new()).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            eprintln!("[CLIProxyAPI] New config failed ({}), restoring previous one", e);
+            Self::blocking_with(guard, move |m| m.restore_config(previous, previous_port)).await.1?;
+            return Err(format!(
+                "CLIProxyAPI failed to start with the new config ({}); previous config restored",
+                e
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Put back the config.yaml (and port) that `apply_config` replaced and restart
+    fn restore_config(&self, previous: Option<Vec<u8>>, port: u16) -> Result<(), String> {
+        let _ = self.stop();
+        match previous {
+            Some(bytes) => write_private_atomic(&self.config_path, &bytes)
+                .map_err(|e| format!("Failed to restore config: {}", e))?,
+            None => {
+                let _ = std::fs::remove_file(&self.config_path);
+            }
+        }
+        self.set_port(port);
+        self.start()
+    }
+
+    /// Client for the server's management API (OAuth logins, accounts)
+    pub fn management_client(&self) -> Result<ManagementClient, String> {
+        Ok(ManagementClient::new(self.url.clone(), self.management_secret()?))
+    }
+
+    /// Secret for the management API, generated for this install on first use
+    ///
+    /// CLIProxyAPI replaces the plaintext `secret-key` in config.yaml with a
+    /// hash when it starts, so the secret itself is kept in a separate file.
+    pub fn management_secret(&self) -> Result<String, String> {
+        if let Ok(secret) = std::fs::read_to_string(&self.secret_path) {
+            if !secret.trim().is_empty() {
+                return Ok(secret.trim().to_string());
+            }
+        }
+
+        // Config first: a secret the server never saw would be useless
+        let secret = generate_secret();
+        set_management_secret(&self.config_path, &secret)?;
+        write_private_atomic(&self.secret_path, secret.as_bytes())
+            .map_err(|e| format!("Failed to save management secret: {}", e))?;
+        Ok(secret)
+    }
+
+    /// Where releases come from and downloads are staged
+    fn release_source(&self) -> ReleaseSource {
+        ReleaseSource {
+            api_url: self.release_api_url.clone(),
+            install_dir: self.binary_path.parent().unwrap().to_path_buf(),
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    /// Find the archive for this platform in a release
+    fn platform_asset(release: &GitHubRelease) -> Result<&GitHubAsset, String> {
         let pattern = Self::asset_pattern();
-        let asset = release
+        release
             .assets
             .iter()
-            .find(|a| a.name.contains(pattern) && !a.name.contains(".sha256"))
-            .ok_or_else(|| format!("No binary found for platform: {}", pattern))?;
+            .find(|a| a.name.ends_with(pattern))
+            .ok_or_else(|| format!("No binary found for platform: {}", pattern))
+    }
 
-        Ok((release.tag_name, asset.browser_download_url.clone()))
+    /// Get latest release info from GitHub
+    pub async fn get_latest_release(&self) -> Result<(String, String), String> {
+        let release = self.release_source().fetch_latest_release(&Client::new()).await?;
+        let asset = Self::platform_asset(&release)?;
+
+        Ok((release.tag_name.clone(), asset.browser_download_url.clone()))
     }
 
     /// Download CLIProxyAPI binary from GitHub releases
-    pub async fn download(&self) -> Result<String, String> {
-        let (version, download_url) = self.get_latest_release().await?;
+    ///
+    /// The archive is streamed to a temp file, reporting progress to
+    /// `on_progress`, and checked against the release's published SHA-256
+    /// before anything is extracted. The installed binary is only replaced
+    /// once extraction succeeded. The manager is only locked for the install,
+    /// which runs off the async workers.
+    pub async fn download(
+        manager: &Arc<tokio::sync::Mutex<Self>>,
+        on_progress: &(dyn Fn(DownloadProgress) + Send + Sync),
+    ) -> Result<String, String> {
+        let source = manager.lock().await.release_source();
+        let client = Client::new();
+        let release = source.fetch_latest_release(&client).await?;
+        let archive = source.fetch_archive(&client, &release, on_progress).await?;
 
-        // Create directory
-        let install_dir = self.binary_path.parent().unwrap();
-        fs::create_dir_all(install_dir)
-            .await
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
+        let staged = archive.clone();
+        let result = Self::blocking(manager, move |m| m.install_from_archive(&staged)).await;
+        let _ = std::fs::remove_file(&archive);
+        result.map(|_| release.tag_name)
+    }
+
+    /// Latest release tag, if it is newer than the installed version
+    pub async fn check_update(&self) -> Result<Option<String>, String> {
+        let (latest, _) = self.get_latest_release().await?;
+        Ok(self.newer_release(&latest))
+    }
+
+    /// `latest` if it is newer than the installed version
+    ///
+    /// Returns `None` when either version can't be parsed.
+    fn newer_release(&self, latest: &str) -> Option<String> {
+        let installed = Version::find(&self.get_installed_version()?)?;
+        let available = Version::find(latest)?;
+        (available > installed).then(|| latest.to_string())
+    }
+
+    /// Update to the latest release, rolling back if it doesn't come up
+    ///
+    /// Downloads the new release while the current one keeps running, then
+    /// stops the server, keeps the current binary as a backup, installs the
+    /// new release, starts it and waits for `/v1/models` to answer. On
+    /// failure the backup is restored. The server is left running only if
+    /// it was running before. Returns the new version, or `None` if already
+    /// up to date.
+    pub async fn update(
+        manager: &Arc<tokio::sync::Mutex<Self>>,
+        on_progress: &(dyn Fn(DownloadProgress) + Send + Sync),
+    ) -> Result<Option<String>, String> {
+        let source = {
+            let manager = manager.lock().await;
+            if !manager.is_installed() {
+                return Err("CLIProxyAPI not installed. Call download() first.".to_string());
+            }
+            manager.release_source()
+        };
 
-        // Download archive
         let client = Client::new();
-        let response = client
-            .get(&download_url)
-            .header("User-Agent", "OpenMusic")
-            .send()
-            .await
-            .map_err(|e| format!("Failed to download: {}", e))?;
+        let release = source.fetch_latest_release(&client).await?;
+        let latest = release.tag_name.clone();
+        if Self::blocking(manager, move |m| m.newer_release(&latest)).await.is_none() {
+            return Ok(None);
+        }
+        let archive = source.fetch_archive(&client, &release, on_progress).await?;
 
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(|e| format!("Failed to read bytes: {}", e))?;
+        // Held until the new version answers, so the supervisor can't restart the old one
+        let guard = manager.clone().lock_owned().await;
+        let staged = archive.clone();
+        let (guard, swapped) = Self::blocking_with(guard, move |m| m.swap_binary(&staged)).await;
+        let _ = std::fs::remove_file(&archive);
+        let was_running = swapped?;
 
-        // Extract archive based on platform
-        #[cfg(target_os = "windows")]
-        {
-            // Extract ZIP on Windows
-            let cursor = Cursor::new(bytes.as_ref());
-            let mut archive = zip::ZipArchive::new(cursor)
-                .map_err(|e| format!("Failed to open zip: {}", e))?;
-
-            for i in 0..archive.len() {
-                let mut file = archive.by_index(i)
-                    .map_err(|e| format!("Failed to read zip entry: {}", e))?;
-
-                let name = file.name().to_string();
-                if name.ends_with(".exe") {
-                    let mut contents = Vec::new();
-                    file.read_to_end(&mut contents)
-                        .map_err(|e| format!("Failed to read exe: {}", e))?;
-                    std::fs::write(&self.binary_path, contents)
-                        .map_err(|e| format!("Failed to write binary: {}", e))?;
-                    break;
-                }
+        let (guard, started) = Self::blocking_with(guard, |m| m.start()).await;
+        let health = match started {
+            Ok(()) => guard.wait_until_healthy(&client).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = health {
+            eprintln!("[CLIProxyAPI] {} failed to start ({}), rolling back", release.tag_name, e);
+            Self::blocking_with(guard, move |m| m.roll_back(was_running)).await.1?;
+            return Err(format!(
+                "Update to {} failed ({}); rolled back to the previous version",
+                release.tag_name, e
+            ));
+        }
+
+        if !was_running {
+            Self::blocking_with(guard, |m| m.stop()).await.1?;
+        }
+        Ok(Some(release.tag_name))
+    }
+
+    /// Stop the server and install `archive`, keeping the current binary as a backup
+    ///
+    /// Returns whether the server was running. If installing fails the
+    /// current binary stays, and is restarted if it was running.
+    fn swap_binary(&self, archive: &Path) -> Result<bool, String> {
+        let was_running = self.is_running();
+        self.stop()?;
+
+        std::fs::copy(&self.binary_path, self.backup_path())
+            .map_err(|e| format!("Failed to back up binary: {}", e))?;
+
+        if let Err(e) = self.install_binary(archive) {
+            if was_running {
+                self.start()?;
             }
+            return Err(e);
         }
+        Ok(was_running)
+    }
 
-        #[cfg(unix)]
-        {
-            // Extract tar.gz on Unix
-            use flate2::read::GzDecoder;
-            use tar::Archive;
-
-            let cursor = Cursor::new(bytes.as_ref());
-            let gz = GzDecoder::new(cursor);
-            let mut archive = Archive::new(gz);
-
-            for entry in archive.entries().map_err(|e| format!("Failed to read tar: {}", e))? {
-                let mut entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-                let path = entry.path().map_err(|e| format!("Failed to get path: {}", e))?;
-
-                if let Some(name) = path.file_name() {
-                    if name == "CLIProxyAPI" {
-                        entry.unpack(&self.binary_path)
-                            .map_err(|e| format!("Failed to extract: {}", e))?;
-                        break;
+    /// Restore the backup made by `swap_binary`
+    fn roll_back(&self, was_running: bool) -> Result<(), String> {
+        let _ = self.stop();
+        std::fs::rename(self.backup_path(), &self.binary_path)
+            .map_err(|e| format!("Failed to restore previous binary: {}", e))?;
+        if was_running {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Run blocking work (starting or stopping the process) off the async workers
+    ///
+    /// The manager stays locked until `f` returns.
+    pub async fn blocking<T: Send + 'static>(
+        manager: &Arc<tokio::sync::Mutex<Self>>,
+        f: impl FnOnce(&Self) -> T + Send + 'static,
+    ) -> T {
+        Self::blocking_with(manager.clone().lock_owned().await, f).await.1
+    }
+
+    /// `blocking` with a lock already held, handing it back afterwards
+    async fn blocking_with<T: Send + 'static>(
+        guard: OwnedMutexGuard<Self>,
+        f: impl FnOnce(&Self) -> T + Send + 'static,
+    ) -> (OwnedMutexGuard<Self>, T) {
+        tokio::task::spawn_blocking(move || {
+            let result = f(&guard);
+            (guard, result)
+        })
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+
+    /// Where the previous binary is kept during and after an update
+    fn backup_path(&self) -> PathBuf {
+        self.binary_path.with_file_name(format!("{}.bak", Self::binary_name()))
+    }
+
+    /// Poll `/v1/models` until the server answers or `health_timeout` passes
+    async fn wait_until_healthy(&self, client: &Client) -> Result<(), String> {
+        let deadline = Instant::now() + self.health_timeout;
+
+        loop {
+            if self.health_check(client).await {
+                return Ok(());
+            }
+            if !self.process_alive() {
+                return Err("the process exited".to_string());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(format!("no healthy response within {:?}", self.health_timeout));
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    /// Whether the server answers `/v1/models` successfully
+    async fn health_check(&self, client: &Client) -> bool {
+        server_healthy(client, &self.get_url()).await
+    }
+
+    /// Watch the server and restart it after crashes
+    ///
+    /// Runs until the task is dropped. Only acts while the server is meant
+    /// to be running (between `start` and `stop`). A process that exits, or
+    /// stops answering `/v1/models` for `unhealthy_polls` polls, is
+    /// restarted with exponential backoff, up to `max_restarts` times in a
+    /// row. Status changes are passed to `on_status`.
+    pub async fn supervise(
+        manager: Arc<tokio::sync::Mutex<Self>>,
+        policy: SupervisorPolicy,
+        on_status: impl Fn(&SupervisorStatus) + Send + Sync + 'static,
+    ) {
+        let client = Client::new();
+        let mut last: Option<SupervisorStatus> = None;
+        let mut restarts = 0;
+        let mut failed_polls = 0;
+        let mut gave_up = false;
+        let mut delay = policy.poll_interval;
+
+        loop {
+            tokio::time::sleep(delay).await;
+            delay = policy.poll_interval;
+
+            // Only snapshots are taken under the lock; the health check runs without it
+            let (keep_alive, alive, url) = Self::blocking(&manager, |m| {
+                (m.keep_alive.load(Ordering::Relaxed), m.process_alive(), m.get_url())
+            })
+            .await;
+            let mut error = None;
+
+            let state = if !keep_alive {
+                restarts = 0;
+                failed_polls = 0;
+                if gave_up { ServerState::Failed } else { ServerState::Stopped }
+            } else if !alive {
+                gave_up = false;
+                failed_polls = 0;
+                if restarts >= policy.max_restarts {
+                    eprintln!("[CLIProxyAPI] Giving up after {} restarts", restarts);
+                    manager.lock().await.keep_alive.store(false, Ordering::Relaxed);
+                    gave_up = true;
+                    error = Some(format!("Gave up after {} restarts", restarts));
+                    ServerState::Failed
+                } else {
+                    restarts += 1;
+                    eprintln!("[CLIProxyAPI] Process exited, restarting (attempt {})", restarts);
+                    // `start` moves to another port if something took ours meanwhile;
+                    // skipped if the server was stopped since the snapshot
+                    let started = Self::blocking(&manager, |m| {
+                        if m.keep_alive.load(Ordering::Relaxed) { m.start() } else { Ok(()) }
+                    })
+                    .await;
+                    if let Err(e) = started {
+                        error = Some(e);
                     }
+                    delay = policy.backoff(restarts);
+                    ServerState::Restarting
+                }
+            } else if server_healthy(&client, &url).await {
+                gave_up = false;
+                restarts = 0;
+                failed_polls = 0;
+                ServerState::Running
+            } else {
+                failed_polls += 1;
+                let hung = failed_polls >= policy.unhealthy_polls;
+                if hung {
+                    // Kill it so the next poll restarts it
+                    eprintln!("[CLIProxyAPI] No healthy response for {} polls, restarting", failed_polls);
+                    failed_polls = 0;
+                    delay = Duration::ZERO;
                 }
+                Self::blocking(&manager, move |m| {
+                    if hung && m.keep_alive.load(Ordering::Relaxed) {
+                        let _ = m.stop();
+                        m.keep_alive.store(true, Ordering::Relaxed);
+                    }
+                    match m.server_state() {
+                        ServerState::Starting => ServerState::Starting,
+                        _ => ServerState::Unhealthy,
+                    }
+                })
+                .await
+            };
+
+            let status = SupervisorStatus { state, restarts, error };
+            if last.as_ref() != Some(&status) {
+                on_status(&status);
+                last = Some(status);
             }
+        }
+    }
 
-            // Make executable
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = std::fs::metadata(&self.binary_path)
-                .map_err(|e| format!("Failed to get metadata: {}", e))?
-                .permissions();
-            perms.set_mode(0o755);
-            std::fs::set_permissions(&self.binary_path, perms)
-                .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    /// Install CLIProxyAPI from a local release archive (.tar.gz or .zip)
+    ///
+    /// For offline machines: the archive must contain the CLIProxyAPI binary.
+    /// Creates the default config like `download` does.
+    /// Blocks while extracting; run it through `blocking`.
+    pub fn install_from_archive(&self, archive_path: &Path) -> Result<(), String> {
+        if !archive_path.is_file() {
+            return Err(format!("Archive not found: {}", archive_path.display()));
         }
 
+        let install_dir = self.binary_path.parent().unwrap();
+        std::fs::create_dir_all(install_dir)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+        self.install_binary(archive_path)?;
+
         // Create default config if not exists
         if !self.config_path.exists() {
-            self.create_default_config().await?;
+            self.create_default_config()?;
         }
 
-        Ok(version)
+        Ok(())
     }
 
-    /// Create default config.yaml
-    async fn create_default_config(&self) -> Result<(), String> {
-        let config = format!(
-            r#"# CLIProxyAPI Configuration for OpenMusic
-host: 127.0.0.1
-port: {}
-log-level: info
-auth-dir: ~/.openmusic-cliproxyapi
+    /// Extract the binary next to the installed one, then swap it into place
+    fn install_binary(&self, archive_path: &Path) -> Result<(), String> {
+        let staged = self.binary_path.with_file_name(format!(".{}.new", Self::binary_name()));
 
-# Management API - required for OAuth login
-remote-management:
-  allow-remote: false
-  secret-key: openmusic-local
-"#,
-            self.port
-        );
+        let result = extract_binary(archive_path, &staged).and_then(|_| {
+            std::fs::rename(&staged, &self.binary_path)
+                .map_err(|e| format!("Failed to replace binary: {}", e))
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&staged);
+        }
+        result
+    }
 
-        fs::write(&self.config_path, config)
-            .await
-            .map_err(|e| format!("Failed to write config: {}", e))?;
+    /// Create default config.yaml with a fresh management secret
+    fn create_default_config(&self) -> Result<(), String> {
+        let config = CLIProxyAPIConfig {
+            port: self.port(),
+            ..CLIProxyAPIConfig::default()
+        };
+        config.save(&self.config_path)?;
+        // A new config means the old secret (if any) is gone with it
+        let _ = std::fs::remove_file(&self.secret_path);
+        self.management_secret()?;
 
-        // Create auth-dir in user home
-        if let Some(home) = dirs::home_dir() {
-            let auth_dir = home.join(".openmusic-cliproxyapi");
-            fs::create_dir_all(&auth_dir)
-                .await
-                .map_err(|e| format!("Failed to create auth dir: {}", e))?;
-        }
+        std::fs::create_dir_all(config.auth_dir_path())
+            .map_err(|e| format!("Failed to create auth dir: {}", e))?;
 
         Ok(())
     }
 
     /// Start CLIProxyAPI server
+    ///
+    /// Adopts an instance left running by a previous app session (tracked
+    /// through the PID file) instead of starting a second one. Output of
+    /// processes we start is captured in `log`; adopted ones aren't.
     pub fn start(&self) -> Result<(), String> {
         if !self.is_installed() {
             return Err("CLIProxyAPI not installed. Call download() first.".to_string());
@@ -239,66 +907,184 @@ remote-management:
         if let Some(ref mut child) = *process_guard {
             match child.try_wait() {
                 Ok(Some(_)) => {} // Process exited, we can start new one
-                Ok(None) => return Ok(()), // Already running
+                Ok(None) => {
+                    // Already running
+                    self.keep_alive.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
                 Err(_) => {}
             }
         }
 
-        let child = Command::new(&self.binary_path)
+        if let Some(pid) = self.tracked_pid() {
+            eprintln!("[CLIProxyAPI] Adopting running instance (pid {})", pid);
+            self.keep_alive.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.claim_port()?;
+        // Installs from before per-install secrets still have the shared default
+        self.management_secret()?;
+
+        let mut child = Command::new(&self.binary_path)
             .current_dir(self.binary_path.parent().unwrap())
             .arg("--config")
             .arg(&self.config_path)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
             .map_err(|e| format!("Failed to start CLIProxyAPI: {}", e))?;
 
+        if let Some(stdout) = child.stdout.take() {
+            let log = self.log.clone();
+            std::thread::spawn(move || log.capture("stdout", stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let log = self.log.clone();
+            std::thread::spawn(move || log.capture("stderr", stderr));
+        }
+
+        if let Err(e) = std::fs::write(&self.pid_path, child.id().to_string()) {
+            eprintln!("[CLIProxyAPI] Failed to write PID file: {}", e);
+        }
+
         *process_guard = Some(child);
+        self.keep_alive.store(true, Ordering::Relaxed);
         Ok(())
     }
 
     /// Stop CLIProxyAPI server
+    ///
+    /// Stops our child process, or an instance adopted through the PID
+    /// file. Asks it to exit first and only kills it after `STOP_TIMEOUT`.
+    /// Processes that aren't running our binary are never signalled.
     pub fn stop(&self) -> Result<(), String> {
-        // First try to kill via process handle if we have one
+        self.keep_alive.store(false, Ordering::Relaxed);
+        let mut process_guard = self.process.lock().unwrap();
+
+        if let Some(mut child) = process_guard.take() {
+            if matches!(child.try_wait(), Ok(None)) {
+                self.terminate(child.id());
+            }
+            let _ = child.wait();
+        } else if let Some(pid) = self.tracked_pid() {
+            self.terminate(pid);
+        }
+
+        let _ = std::fs::remove_file(&self.pid_path);
+        Ok(())
+    }
+
+    /// PID of a running instance of our binary recorded in the PID file
+    fn tracked_pid(&self) -> Option<u32> {
+        let pid = std::fs::read_to_string(&self.pid_path).ok()?.trim().parse().ok()?;
+        self.runs_our_binary(pid).then_some(pid)
+    }
+
+    /// Whether `pid` is a live process executing `binary_path`
+    ///
+    /// Guards against PID reuse: a recorded PID may belong to an unrelated
+    /// process by now.
+    fn runs_our_binary(&self, pid: u32) -> bool {
+        #[cfg(target_os = "linux")]
         {
-            let mut process_guard = self.process.lock().unwrap();
-            if let Some(ref mut child) = *process_guard {
-                let _ = child.kill();
-                let _ = child.wait();
-                *process_guard = None;
+            // Replaced binaries (after an update) show up as "<path> (deleted)"
+            let Ok(exe) = std::fs::read_link(format!("/proc/{}/exe", pid)) else {
+                return false;
+            };
+            let exe = exe.to_string_lossy();
+            let exe = Path::new(exe.trim_end_matches(" (deleted)"));
+            if same_file_path(exe, &self.binary_path) {
+                return true;
             }
+
+            // A script runs as its interpreter, with the script path as first argument
+            let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+            cmdline
+                .split(|&b| b == 0)
+                .nth(1)
+                .is_some_and(|arg| same_file_path(Path::new(&*String::from_utf8_lossy(arg)), &self.binary_path))
         }
 
-        // Also kill any orphan process by name (handles app restart case)
-        #[cfg(target_os = "windows")]
+        #[cfg(target_os = "macos")]
         {
-            let _ = Command::new("taskkill")
-                .args(["/F", "/IM", "cliproxyapi.exe"])
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status();
+            let output = Command::new("ps")
+                .args(["-p", &pid.to_string(), "-o", "comm="])
+                .output();
+            match output {
+                Ok(output) if output.status.success() => {
+                    let command = String::from_utf8_lossy(&output.stdout);
+                    same_file_path(Path::new(command.trim()), &self.binary_path)
+                }
+                _ => false,
+            }
         }
 
-        #[cfg(unix)]
+        #[cfg(target_os = "windows")]
         {
-            let _ = Command::new("pkill")
-                .arg("-f")
-                .arg("cliproxyapi")
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .status();
+            // tasklist only reports the image name
+            let output = Command::new("tasklist")
+                .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+                .output();
+            match output {
+                Ok(output) => String::from_utf8_lossy(&output.stdout)
+                    .to_ascii_lowercase()
+                    .starts_with(&format!("\"{}\",", Self::binary_name())),
+                Err(_) => false,
+            }
         }
+    }
 
-        Ok(())
+    /// Ask a process running our binary to exit, killing it after `STOP_TIMEOUT`
+    fn terminate(&self, pid: u32) {
+        send_signal(pid, false);
+
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while self.runs_our_binary(pid) {
+            if Instant::now() >= deadline {
+                eprintln!("[CLIProxyAPI] pid {} ignored termination, killing it", pid);
+                send_signal(pid, true);
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
     }
 
-    /// Check if server is running by checking if port is responding
-    pub fn is_running(&self) -> bool {
-        // Check via TCP connection (more reliable than process handle)
+    /// Whether our server process is alive (started here or adopted)
+    fn process_alive(&self) -> bool {
+        let mut process_guard = self.process.lock().unwrap();
+        if let Some(child) = process_guard.as_mut() {
+            if matches!(child.try_wait(), Ok(None)) {
+                return true;
+            }
+        }
+        self.tracked_pid().is_some()
+    }
+
+    /// Whether something accepts connections on the server port
+    fn port_open(&self) -> bool {
         std::net::TcpStream::connect_timeout(
-            &format!("127.0.0.1:{}", self.port).parse().unwrap(),
-            std::time::Duration::from_millis(500),
-        ).is_ok()
+            &format!("127.0.0.1:{}", self.port()).parse().unwrap(),
+            Duration::from_millis(500),
+        )
+        .is_ok()
+    }
+
+    /// Current server state, telling our process apart from another one on the port
+    pub fn server_state(&self) -> ServerState {
+        match (self.process_alive(), self.port_open()) {
+            (true, true) => ServerState::Running,
+            (true, false) => ServerState::Starting,
+            (false, true) => ServerState::PortInUse,
+            (false, false) => ServerState::Stopped,
+        }
+    }
+
+    /// Check if our server process is up and accepting connections
+    ///
+    /// `false` if the port is held by some other process; see `server_state`.
+    pub fn is_running(&self) -> bool {
+        self.server_state() == ServerState::Running
     }
 
     /// Get installed version (reads from binary --version)
@@ -316,8 +1102,767 @@ remote-management:
     }
 }
 
+/// Extract the hex digest for `file_name` from a sha256sum-style listing
+///
+/// Accepts a bare digest or lines of `<digest>  <file name>`.
+fn parse_checksum(text: &str, file_name: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let digest = parts.next()?;
+        let listed = parts.next().map(|name| name.trim_start_matches('*'));
+
+        let for_file = listed.is_none_or(|name| name.rsplit('/').next() == Some(file_name));
+        let valid = digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit());
+        (for_file && valid).then(|| digest.to_ascii_lowercase())
+    })
+}
+
+/// Release version, ordered like semver (pre-releases sort before their release)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Option<String>,
+}
+
+impl Version {
+    /// Find the first version in text such as "v6.1.0" or
+    /// "CLIProxyAPI Version: 6.1.0, Commit: abc123"
+    fn find(text: &str) -> Option<Self> {
+        text.split(|c: char| c.is_whitespace() || c == ',')
+            .find_map(Self::parse)
+    }
+
+    /// Parse "1.2.3", "v1.2", "1.2.3-rc.1+build" and the like
+    fn parse(token: &str) -> Option<Self> {
+        let token = token
+            .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+            .trim_start_matches(['v', 'V']);
+        let token = token.split('+').next()?;
+        let (core, pre) = match token.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (token, None),
+        };
+
+        let mut parts = core.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = match parts.next() {
+            Some(patch) => patch.parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self { major, minor, patch, pre: pre.filter(|p| !p.is_empty()) })
+    }
+
+    /// Compare pre-release tags like semver: dot-separated identifiers in
+    /// turn, numeric ones by value and below alphanumeric ones
+    fn compare_pre(a: &str, b: &str) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        let (mut a, mut b) = (a.split('.'), b.split('.'));
+        loop {
+            let (x, y) = match (a.next(), b.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(x), Some(y)) => (x, y),
+            };
+            let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => x.cmp(y),
+            };
+            if order != Ordering::Equal {
+                return order;
+            }
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => Self::compare_pre(a, b),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Lowercase hex encoding of a digest
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Check a downloaded archive's SHA-256 against the expected one (lowercase hex)
+fn verify_checksum(actual: &str, expected: &str, name: &str) -> Result<(), String> {
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {} (expected {}, got {}); refusing to install",
+            name, expected, actual
+        ));
+    }
+    Ok(())
+}
+
+/// Whether two paths name the same file (comparing canonical forms when possible)
+fn same_file_path(a: &Path, b: &Path) -> bool {
+    let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    a == b || canonical(a) == canonical(b)
+}
+
+/// Send a termination request (or a forced kill) to a process
+fn send_signal(pid: u32, force: bool) {
+    #[cfg(unix)]
+    let mut command = {
+        let mut command = Command::new("kill");
+        command.arg(if force { "-KILL" } else { "-TERM" }).arg(pid.to_string());
+        command
+    };
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("taskkill");
+        command.args(["/PID", &pid.to_string()]);
+        if force {
+            command.arg("/F");
+        }
+        command
+    };
+
+    let _ = command.stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
+/// Whether an archive entry is the CLIProxyAPI binary
+fn is_binary_entry(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+
+    #[cfg(target_os = "windows")]
+    {
+        name.to_ascii_lowercase().ends_with(".exe")
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        name == "CLIProxyAPI"
+    }
+}
+
+/// Extract the CLIProxyAPI binary from a release archive to `dest`
+///
+/// The format (.zip or .tar.gz/.tgz) is taken from the archive's file name.
+fn extract_binary(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let archive_name = archive_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let file = std::fs::File::open(archive_path)
+        .map_err(|e| format!("Failed to open archive: {}", e))?;
+
+    let found = if archive_name.ends_with(".zip") {
+        extract_from_zip(file, dest)?
+    } else if archive_name.ends_with(".tar.gz") || archive_name.ends_with(".tgz") {
+        extract_from_tar_gz(file, dest)?
+    } else {
+        return Err(format!(
+            "Unsupported archive format: {} (expected .zip or .tar.gz)",
+            archive_path.display()
+        ));
+    };
+
+    if !found {
+        return Err("CLIProxyAPI binary not found in archive".to_string());
+    }
+
+    #[cfg(unix)]
+    {
+        // Make executable
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(dest)
+            .map_err(|e| format!("Failed to get metadata: {}", e))?
+            .permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(dest, perms)
+            .map_err(|e| format!("Failed to set permissions: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Extract the binary from a ZIP archive; returns whether it was found
+fn extract_from_zip(file: std::fs::File, dest: &Path) -> Result<bool, String> {
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Failed to open zip: {}", e))?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)
+            .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+
+        if file.is_file() && is_binary_entry(Path::new(file.name())) {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)
+                .map_err(|e| format!("Failed to read binary: {}", e))?;
+            std::fs::write(dest, contents)
+                .map_err(|e| format!("Failed to write binary: {}", e))?;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Extract the binary from a gzipped tarball; returns whether it was found
+fn extract_from_tar_gz(file: std::fs::File, dest: &Path) -> Result<bool, String> {
+    use flate2::read::GzDecoder;
+    use tar::Archive;
+
+    let gz = GzDecoder::new(file);
+    let mut archive = Archive::new(gz);
+
+    for entry in archive.entries().map_err(|e| format!("Failed to read tar: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+        let path = entry.path().map_err(|e| format!("Failed to get path: {}", e))?;
+
+        if entry.header().entry_type().is_file() && is_binary_entry(&path) {
+            entry.unpack(dest)
+                .map_err(|e| format!("Failed to extract: {}", e))?;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Whether nothing is listening on `port` locally
+fn port_available(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// A port that is free right now, as picked by the OS
+fn free_port() -> Result<u16, String> {
+    std::net::TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|e| format!("Failed to find a free port: {}", e))
+}
+
+impl Default for CLIProxyAPIManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for CLIProxyAPIManager {
     fn drop(&mut self) {
         let _ = self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{StubResponse, StubServer, TempDir};
+    use serde_json::json;
+    use std::io::{Cursor, Write};
+
+    const BINARY: &[u8] = b"#!/bin/sh\necho CLIProxyAPI 6.1.0\n";
+
+    /// Release archive containing a fake CLIProxyAPI binary
+    fn archive() -> Vec<u8> {
+        archive_of(BINARY)
+    }
+
+    /// Release archive containing `binary` as CLIProxyAPI
+    fn archive_of(binary: &[u8]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(binary.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "CLIProxyAPI", binary).unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Install a shell script as the CLIProxyAPI binary
+    #[cfg(unix)]
+    fn install_script(manager: &CLIProxyAPIManager, body: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::write(&manager.binary_path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&manager.binary_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn sha256_hex(bytes: &[u8]) -> String {
+        hex(&Sha256::digest(bytes))
+    }
+
+    /// Stub release API serving `archive` with the given checksum listing
+    async fn release_server(archive: Vec<u8>, checksums: &str) -> StubServer {
+        let server = StubServer::start().await;
+        let name = format!("CLIProxyAPI_6.1.0_{}", CLIProxyAPIManager::asset_pattern());
+        let release = json!({
+            "tag_name": "v6.1.0",
+            "assets": [
+                { "name": name, "browser_download_url": format!("{}/download/archive", server.url()) },
+                { "name": "checksums.txt", "browser_download_url": format!("{}/download/checksums", server.url()) }
+            ]
+        });
+        server.on("GET", "/releases/latest", StubResponse::json(200, release));
+        server.on("GET", "/download/archive", StubResponse::bytes(200, archive));
+        server.on("GET", "/download/checksums", StubResponse::raw(200, &checksums.replace("{name}", &name)));
+        server
+    }
+
+    /// Manager installing into a fresh temp dir (with a config, so none is generated)
+    fn manager(server: &StubServer) -> (CLIProxyAPIManager, TempDir) {
+        let dir = TempDir::new("cliproxyapi");
+        std::fs::write(dir.join("config.yaml"), "port: 8317\n").unwrap();
+
+        let mut manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+        manager.set_release_api_url(server.url().to_string());
+        (manager, dir)
+    }
+
+    fn shared(manager: CLIProxyAPIManager) -> Arc<tokio::sync::Mutex<CLIProxyAPIManager>> {
+        Arc::new(tokio::sync::Mutex::new(manager))
+    }
+
+    #[test]
+    fn parses_sha256sum_listings() {
+        let digest = "A".repeat(64);
+        let listing = format!("{}  other.tar.gz\n{} *dist/app.tar.gz\n", "b".repeat(64), digest);
+
+        assert_eq!(parse_checksum(&listing, "app.tar.gz"), Some("a".repeat(64)));
+        assert_eq!(parse_checksum(&"c".repeat(64), "app.tar.gz"), Some("c".repeat(64)));
+        assert_eq!(parse_checksum("not-a-digest  app.tar.gz", "app.tar.gz"), None);
+        assert_eq!(parse_checksum(&listing, "missing.tar.gz"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn installs_verified_archive() {
+        let archive = archive();
+        let server = release_server(archive.clone(), &format!("{}  {{name}}\n", sha256_hex(&archive))).await;
+        let (manager, dir) = manager(&server);
+        let binary = manager.binary_path.clone();
+        let manager = shared(manager);
+
+        // The manager stays usable while the archive downloads
+        let reports = Mutex::new(Vec::new());
+        let version = CLIProxyAPIManager::download(&manager, &|progress| {
+            assert!(manager.try_lock().is_ok());
+            reports.lock().unwrap().push(progress);
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(version, "v6.1.0");
+        assert_eq!(std::fs::read(&binary).unwrap(), BINARY);
+
+        let last = reports.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(last.received, archive.len() as u64);
+        assert_eq!(last.total, Some(archive.len() as u64));
+
+        // Only the binary and config are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn refuses_checksum_mismatch() {
+        let server = release_server(archive(), &format!("{}  {{name}}\n", "0".repeat(64))).await;
+        let (manager, _dir) = manager(&server);
+        let manager = shared(manager);
+
+        let error = CLIProxyAPIManager::download(&manager, &|_| {}).await.unwrap_err();
+
+        assert!(error.contains("Checksum mismatch"), "{}", error);
+        assert!(!manager.lock().await.is_installed());
+    }
+
+    #[tokio::test]
+    async fn refuses_archive_without_checksum() {
+        let server = release_server(archive(), "").await;
+        let (manager, _dir) = manager(&server);
+
+        let error = CLIProxyAPIManager::download(&shared(manager), &|_| {}).await.unwrap_err();
+
+        assert!(error.contains("No checksum"), "{}", error);
+        assert!(server.requests_to("/download/archive").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cancelled_download_keeps_installed_binary() {
+        let archive = archive();
+        let server = release_server(archive.clone(), &format!("{}  {{name}}\n", sha256_hex(&archive))).await;
+        let (manager, dir) = manager(&server);
+        let binary = manager.binary_path.clone();
+        std::fs::write(&binary, b"previous").unwrap();
+
+        let cancel = manager.download_cancel();
+        let error = CLIProxyAPIManager::download(&shared(manager), &|_| cancel.cancel()).await.unwrap_err();
+
+        assert_eq!(error, "Download cancelled");
+        assert_eq!(std::fs::read(&binary).unwrap(), b"previous");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn installs_from_local_archives() {
+        let dir = TempDir::new("cliproxyapi");
+        std::fs::write(dir.join("config.yaml"), "port: 8317\n").unwrap();
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+
+        let tarball = dir.join("CLIProxyAPI_6.1.0_linux_amd64.tar.gz");
+        std::fs::write(&tarball, archive()).unwrap();
+        manager.install_from_archive(&tarball).unwrap();
+        assert_eq!(std::fs::read(&manager.binary_path).unwrap(), BINARY);
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("dist/CLIProxyAPI", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"from zip").unwrap();
+        let zipped = dir.join("offline.zip");
+        std::fs::write(&zipped, zip.finish().unwrap().into_inner()).unwrap();
+        manager.install_from_archive(&zipped).unwrap();
+        assert_eq!(std::fs::read(&manager.binary_path).unwrap(), b"from zip");
+    }
+
+    #[tokio::test]
+    async fn rejects_archives_without_the_binary() {
+        let dir = TempDir::new("cliproxyapi");
+        std::fs::write(dir.join("config.yaml"), "port: 8317\n").unwrap();
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("README.md", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"docs only").unwrap();
+        let zipped = dir.join("docs.zip");
+        std::fs::write(&zipped, zip.finish().unwrap().into_inner()).unwrap();
+
+        let error = manager.install_from_archive(&zipped).unwrap_err();
+        assert!(error.contains("not found in archive"), "{}", error);
+
+        let error = manager.install_from_archive(&dir.join("config.yaml")).unwrap_err();
+        assert!(error.contains("Unsupported archive format"), "{}", error);
+
+        assert!(!manager.is_installed());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn compares_versions_semver_style() {
+        let v = |text: &str| Version::find(text).unwrap();
+
+        assert_eq!(v("CLIProxyAPI Version: 6.1.0, Commit: abc123"), v("v6.1.0"));
+        assert!(v("v6.10.0") > v("v6.9.2"));
+        assert!(v("6.1.0") > v("6.1.0-rc.1"));
+        assert!(v("6.1.0-rc.2") < v("6.1.0-rc.10"));
+        assert!(v("6.1.0-rc.1") < v("6.1.0-rc.1.1"));
+        assert!(v("6.1.0-1") < v("6.1.0-alpha"));
+        assert!(v("6.1.0-alpha") < v("6.1.0-beta"));
+        assert!(v("v6.1") < v("6.1.1"));
+        assert_eq!(v("(6.2.0+build.7)"), v("6.2.0"));
+        assert!(Version::find("dev build").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reports_only_newer_releases() {
+        let server = release_server(archive(), "").await;
+        let (manager, _dir) = manager(&server);
+        install_script(&manager, "echo 'CLIProxyAPI Version: 6.0.9, Commit: abc'");
+
+        assert_eq!(manager.check_update().await.unwrap().as_deref(), Some("v6.1.0"));
+        assert_eq!(manager.newer_release("6.0.9"), None);
+        assert_eq!(manager.newer_release("v6.0.8"), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn updates_and_keeps_the_previous_binary() {
+        let new_binary = b"#!/bin/sh\ntouch \"$(dirname \"$0\")/started\"\nwhile :; do sleep 1; done\n";
+        let archive = archive_of(new_binary);
+        let server = release_server(archive.clone(), &format!("{}  {{name}}\n", sha256_hex(&archive))).await;
+        let (mut manager, dir) = manager(&server);
+        manager.health_timeout = Duration::from_secs(5);
+        let port = free_port().unwrap();
+        manager.set_port(port);
+        install_script(&manager, "echo 'CLIProxyAPI Version: 6.0.9, Commit: abc'");
+        let previous = std::fs::read(&manager.binary_path).unwrap();
+        let (binary, backup) = (manager.binary_path.clone(), manager.backup_path());
+
+        // Answer the health check for the new version once it has started
+        let started = dir.join("started");
+        let api = tokio::spawn(async move {
+            while !started.exists() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let api = StubServer::start_on(port).await;
+            api.on("GET", "/v1/models", StubResponse::json(200, json!({ "data": [] })));
+            std::future::pending::<()>().await;
+        });
+        let manager = shared(manager);
+
+        let version = CLIProxyAPIManager::update(&manager, &|_| {}).await;
+        api.abort();
+
+        assert_eq!(version.unwrap().as_deref(), Some("v6.1.0"));
+        assert_eq!(std::fs::read(&binary).unwrap(), new_binary);
+        assert_eq!(std::fs::read(&backup).unwrap(), previous);
+        // It wasn't running before the update, so it isn't left running
+        assert!(!manager.lock().await.process_alive());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rolls_back_when_the_new_version_does_not_come_up() {
+        let archive = archive_of(b"#!/bin/sh\nexit 1\n");
+        let server = release_server(archive.clone(), &format!("{}  {{name}}\n", sha256_hex(&archive))).await;
+        let (manager, _dir) = manager(&server);
+        manager.set_port(free_port().unwrap());
+        install_script(
+            &manager,
+            "case \"$1\" in --version) echo 'CLIProxyAPI Version: 6.0.9'; exit 0;; esac\nwhile :; do sleep 1; done",
+        );
+        let previous = std::fs::read(&manager.binary_path).unwrap();
+        manager.start().unwrap();
+        // Stands in for the old version's listener, so it counts as running
+        let _listener = std::net::TcpListener::bind(("127.0.0.1", manager.port())).unwrap();
+        assert!(manager.is_running());
+        let manager = shared(manager);
+
+        let error = CLIProxyAPIManager::update(&manager, &|_| {}).await.unwrap_err();
+
+        assert!(error.contains("rolled back"), "{}", error);
+        let manager = manager.lock().await;
+        assert_eq!(std::fs::read(&manager.binary_path).unwrap(), previous);
+        assert!(!manager.backup_path().exists());
+        assert!(manager.process_alive(), "the previous version runs again");
+        manager.stop().unwrap();
+    }
+
+    /// Manager whose "binary" ignores its arguments and runs until signalled
+    #[cfg(target_os = "linux")]
+    fn long_running_manager() -> (CLIProxyAPIManager, TempDir) {
+        let dir = TempDir::new("cliproxyapi");
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+        // Not `exec`: the shell has to stay the process so it's recognised as ours
+        install_script(&manager, "echo listening >&2\nwhile :; do sleep 1; done");
+        (manager, dir)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stops_the_process_recorded_in_the_pid_file() {
+        let (manager, _dir) = long_running_manager();
+        manager.start().unwrap();
+
+        let pid = manager.tracked_pid().expect("pid file written");
+        assert!(manager.runs_our_binary(pid));
+
+        // Output is captured by a background thread
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while manager.log().tail(1).is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let captured = manager.log().tail(1);
+        assert_eq!((captured[0].stream.as_str(), captured[0].line.as_str()), ("stderr", "listening"));
+
+        manager.stop().unwrap();
+
+        assert!(!manager.runs_our_binary(pid));
+        assert!(!manager.pid_path.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn adopts_an_instance_from_a_previous_session() {
+        let (previous, dir) = long_running_manager();
+        previous.start().unwrap();
+        let pid = previous.tracked_pid().unwrap();
+        // The app exits without stopping its child
+        drop(previous.process.lock().unwrap().take());
+
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+        manager.start().unwrap();
+        assert_eq!(manager.tracked_pid(), Some(pid));
+        assert!(manager.process.lock().unwrap().is_none());
+
+        manager.stop().unwrap();
+        assert!(!manager.runs_our_binary(pid));
+
+        drop(previous);
+    }
+
+    #[test]
+    fn never_signals_processes_running_other_binaries() {
+        let dir = TempDir::new("cliproxyapi");
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+
+        // A stale PID file now pointing at an unrelated process (this test)
+        std::fs::write(&manager.pid_path, std::process::id().to_string()).unwrap();
+        assert_eq!(manager.tracked_pid(), None);
+
+        manager.stop().unwrap();
+        assert!(!manager.pid_path.exists());
+    }
+
+    #[test]
+    fn reports_foreign_process_on_the_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let dir = TempDir::new("cliproxyapi");
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), port);
+
+        assert_eq!(manager.server_state(), ServerState::PortInUse);
+        assert!(!manager.is_running());
+
+        drop(listener);
+        assert_eq!(manager.server_state(), ServerState::Stopped);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = SupervisorPolicy {
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..SupervisorPolicy::default()
+        };
+
+        let delays: Vec<u64> = (1..=5).map(|n| policy.backoff(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn supervisor_restarts_crashing_server_then_gives_up() {
+        let dir = TempDir::new("cliproxyapi");
+        // Free port, so the crash isn't mistaken for a foreign server
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), port);
+        install_script(&manager, "exit 1");
+        manager.start().unwrap();
+
+        let manager = Arc::new(tokio::sync::Mutex::new(manager));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let policy = SupervisorPolicy {
+            poll_interval: Duration::from_millis(20),
+            max_restarts: 2,
+            base_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(20),
+            unhealthy_polls: 3,
+        };
+        let task = tokio::spawn(CLIProxyAPIManager::supervise(manager.clone(), policy, move |status| {
+            let _ = tx.send(status.clone());
+        }));
+
+        let mut seen = Vec::new();
+        while let Ok(Some(status)) = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await {
+            let failed = status.state == ServerState::Failed;
+            seen.push(status);
+            if failed {
+                break;
+            }
+        }
+        task.abort();
+
+        let states: Vec<(ServerState, u32)> = seen.iter().map(|s| (s.state, s.restarts)).collect();
+        assert_eq!(
+            states,
+            [(ServerState::Restarting, 1), (ServerState::Restarting, 2), (ServerState::Failed, 2)]
+        );
+        assert!(!manager.lock().await.keep_alive.load(Ordering::Relaxed));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn moves_to_a_free_port_when_the_configured_one_is_busy() {
+        let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let busy_port = busy.local_addr().unwrap().port();
+        let dir = TempDir::new("cliproxyapi");
+        std::fs::write(
+            dir.join("config.yaml"),
+            format!("host: 127.0.0.1\nport: {}\nremote-management:\n  allow-remote: false\n", busy_port),
+        )
+        .unwrap();
+
+        let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), busy_port);
+        let url = manager.server_url();
+        install_script(&manager, "exit 0");
+        manager.start().unwrap();
+
+        let port = manager.port();
+        assert_ne!(port, busy_port);
+        assert_eq!(manager.config().unwrap().port, port);
+        let config = std::fs::read_to_string(&manager.config_path).unwrap();
+        assert!(config.contains("host: 127.0.0.1\n") && config.contains("  allow-remote: false\n"));
+        // Providers holding the shared URL follow the move
+        assert_eq!(url.get(), format!("http://localhost:{}", port));
+
+        manager.stop().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn new_installs_get_their_own_management_secret() {
+        let mut secrets = Vec::new();
+        for _ in 0..2 {
+            let dir = TempDir::new("cliproxyapi");
+            let manager = CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317);
+            let tarball = dir.join("CLIProxyAPI_6.1.0_linux_amd64.tar.gz");
+            std::fs::write(&tarball, archive()).unwrap();
+            manager.install_from_archive(&tarball).unwrap();
+
+            let secret = manager.management_secret().unwrap();
+            let config = std::fs::read_to_string(&manager.config_path).unwrap();
+            assert!(config.contains(&format!("secret-key: {}", secret)), "{}", config);
+            assert!(!config.contains("openmusic-local"));
+            assert_eq!(manager.config().unwrap().port, 8317);
+            secrets.push(secret);
+        }
+        assert_ne!(secrets[0], secrets[1]);
+    }
+
+    #[tokio::test]
+    async fn applies_only_valid_config() {
+        let dir = TempDir::new("cliproxyapi");
+        std::fs::write(dir.join("config.yaml"), "port: 8317\n").unwrap();
+        let manager = shared(CLIProxyAPIManager::with_data_dir(dir.path().to_path_buf(), 8317));
+
+        let mut config = manager.lock().await.config().unwrap();
+        config.log_level = "loud".to_string();
+        let error = CLIProxyAPIManager::apply_config(&manager, config.clone()).await.unwrap_err();
+        assert!(error.contains("log level"), "{}", error);
+        assert_eq!(std::fs::read_to_string(dir.join("config.yaml")).unwrap(), "port: 8317\n");
+
+        config.log_level = "debug".to_string();
+        config.port = 9123;
+        config.auth_dir = dir.join("auth").display().to_string();
+        CLIProxyAPIManager::apply_config(&manager, config.clone()).await.unwrap();
+        assert_eq!(manager.lock().await.config().unwrap(), config);
+        assert_eq!(manager.lock().await.get_url(), "http://localhost:9123");
+        assert!(dir.join("auth").is_dir());
+    }
+}
@@ -1,19 +1,76 @@
-use tauri::State;
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
 use tokio::sync::Mutex;
 
+use super::claude_code::{ClaudeCodeAuthStatus, ClaudeCodeProvider};
+use super::config_store::{key_vault_status, reset_key_vault, save_ai_config};
 use super::manager::AIProviderManager;
-use super::types::{AIResponse, ChatMessage};
+use super::provider::ProviderDiagnostics;
+use super::secret::SecretString;
+use super::types::{AIConfig, AIResponse, ChatMessage, GenerationOptions, ModelInfo};
+
+/// How long each provider gets to answer `diagnose_ai_providers`
+const DIAGNOSE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Tauri command to generate AI completions
+///
+/// `task` (e.g., "lyrics", "theory") selects a per-task route from the routing policy.
+/// `bypass_cache` forces a fresh response even when response caching is enabled.
 #[tauri::command]
 pub async fn ai_complete(
     messages: Vec<ChatMessage>,
     model: Option<String>,
+    task: Option<String>,
+    bypass_cache: Option<bool>,
     state: State<'_, Mutex<AIProviderManager>>,
 ) -> Result<AIResponse, String> {
-    let manager = state.lock().await;
+    let options = GenerationOptions {
+        bypass_cache: bypass_cache.unwrap_or(false),
+        ..GenerationOptions::default()
+    };
+
+    let manager = state.lock().await.clone();
     manager
-        .complete_with_model(messages, model)
+        .complete_for_task(messages, model, &options, task.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Streamed text chunk, emitted as `ai-stream` events
+#[derive(Clone, Serialize)]
+pub struct StreamDelta {
+    /// Caller-chosen id to match events to the request
+    pub stream_id: String,
+    pub delta: String,
+}
+
+/// Tauri command to stream an AI completion
+///
+/// Text arrives as `ai-stream` events tagged with `stream_id`; the full
+/// response is returned once the stream ends.
+#[tauri::command]
+pub async fn ai_complete_stream(
+    messages: Vec<ChatMessage>,
+    model: Option<String>,
+    task: Option<String>,
+    stream_id: String,
+    app: AppHandle,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<AIResponse, String> {
+    let on_delta = |delta: &str| {
+        let event = StreamDelta {
+            stream_id: stream_id.clone(),
+            delta: delta.to_string(),
+        };
+        if let Err(e) = app.emit("ai-stream", event) {
+            eprintln!("[AI] Failed to emit stream event: {}", e);
+        }
+    };
+
+    let manager = state.lock().await.clone();
+    manager
+        .complete_stream_for_task(messages, model, &GenerationOptions::default(), task.as_deref(), &on_delta)
         .await
         .map_err(|e| e.to_string())
 }
@@ -29,12 +86,14 @@ pub async fn list_ai_providers(state: State<'_, Mutex<AIProviderManager>>) -> Re
 #[tauri::command]
 pub async fn set_ai_provider(
     name: String,
+    app: AppHandle,
     state: State<'_, Mutex<AIProviderManager>>,
 ) -> Result<(), String> {
     let mut manager = state.lock().await;
     manager
         .set_default_provider(name)
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    save_ai_config(&app, manager.config())
 }
 
 /// Tauri command to get the current default provider
@@ -50,6 +109,85 @@ pub async fn check_ai_provider_availability(
     name: String,
     state: State<'_, Mutex<AIProviderManager>>,
 ) -> Result<bool, String> {
-    let manager = state.lock().await;
+    let manager = state.lock().await.clone();
     Ok(manager.is_provider_available(&name).await)
 }
+
+/// Tauri command to health-check all registered providers
+///
+/// Reports reachability, credentials, configured model and latency per provider.
+#[tauri::command]
+pub async fn diagnose_ai_providers(
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<Vec<ProviderDiagnostics>, String> {
+    let manager = state.lock().await.clone();
+    Ok(manager.diagnose(DIAGNOSE_TIMEOUT).await)
+}
+
+/// Tauri command to report Claude Code OAuth credential status
+///
+/// Lets the UI warn before credentials lapse. Does not refresh tokens.
+#[tauri::command]
+pub async fn get_claude_code_auth_status() -> Result<ClaudeCodeAuthStatus, String> {
+    Ok(ClaudeCodeProvider::new(None).auth_status())
+}
+
+/// Tauri command to list models offered by a provider
+#[tauri::command]
+pub async fn list_ai_models(
+    provider: String,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<Vec<ModelInfo>, String> {
+    let manager = state.lock().await.clone();
+    manager
+        .list_models(&provider)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Tauri command to get the current AI configuration
+///
+/// API keys are write-only and never returned to the frontend.
+#[tauri::command]
+pub async fn get_ai_config(state: State<'_, Mutex<AIProviderManager>>) -> Result<AIConfig, String> {
+    let manager = state.lock().await;
+    Ok(manager.config().clone())
+}
+
+/// Tauri command to update the AI configuration
+///
+/// Rebuilds the provider list and persists the new configuration.
+/// An omitted API key keeps the stored one; an empty key removes it.
+#[tauri::command]
+pub async fn update_ai_config(
+    mut config: AIConfig,
+    app: AppHandle,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<(), String> {
+    let mut manager = state.lock().await;
+    merge_api_key(&mut config.openai_api_key, &manager.config().openai_api_key);
+    merge_api_key(&mut config.anthropic_api_key, &manager.config().anthropic_api_key);
+    manager.update_config(config);
+    save_ai_config(&app, manager.config())
+}
+
+/// Tauri command to check the API key vault; returns the problem if keys can't be read
+#[tauri::command]
+pub async fn get_ai_key_vault_status(app: AppHandle) -> Result<Option<String>, String> {
+    Ok(key_vault_status(&app))
+}
+
+/// Tauri command to set an unreadable key vault aside so keys can be entered again
+#[tauri::command]
+pub async fn reset_ai_key_vault(app: AppHandle) -> Result<(), String> {
+    reset_key_vault(&app)
+}
+
+/// Apply write-only key semantics: omitted keeps `current`, empty removes it
+fn merge_api_key(key: &mut Option<SecretString>, current: &Option<SecretString>) {
+    match key {
+        None => *key = current.clone(),
+        Some(k) if k.is_empty() => *key = None,
+        Some(_) => {}
+    }
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use super::types::TokenUsage;
+
+/// Default time each provider gets to answer a comparison request
+pub const DEFAULT_COMPARE_TIMEOUT_SECS: u64 = 60;
+
+/// One provider/model pair to include in a side-by-side comparison
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareTarget {
+    /// Provider name as listed by `list_ai_providers`
+    pub provider: String,
+    /// Model override (provider default when unset)
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Outcome of one comparison target
+///
+/// Exactly one of `content` and `error` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareResult {
+    pub provider: String,
+    /// Model that answered (or the requested model on failure)
+    pub model: Option<String>,
+    pub content: Option<String>,
+    pub error: Option<String>,
+    /// The provider didn't answer within the timeout
+    pub timed_out: bool,
+    pub latency_ms: u64,
+    pub usage: Option<TokenUsage>,
+    /// Total tokens reported by the provider
+    pub tokens: Option<u32>,
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use super::compare::{CompareResult, CompareTarget, DEFAULT_COMPARE_TIMEOUT_SECS};
+use super::manager::AIProviderManager;
+use super::types::{ChatMessage, GenerationOptions};
+
+/// Tauri command to run the same messages against several providers/models
+///
+/// All targets are called concurrently; each gets `timeout_secs` (default
+/// 60) to answer. Failures are reported per target.
+#[tauri::command]
+pub async fn ai_compare(
+    messages: Vec<ChatMessage>,
+    targets: Vec<CompareTarget>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    timeout_secs: Option<u64>,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<Vec<CompareResult>, String> {
+    if targets.is_empty() {
+        return Err("No comparison targets given".to_string());
+    }
+
+    let options = GenerationOptions {
+        temperature,
+        max_tokens,
+        ..GenerationOptions::default()
+    };
+    let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_COMPARE_TIMEOUT_SECS).max(1));
+
+    let manager = state.lock().await.clone();
+    Ok(manager.compare(messages, &targets, &options, timeout).await)
+}
@@ -0,0 +1,128 @@
+use serde_json::json;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_store::StoreExt;
+
+use super::types::AIConfig;
+use super::vault::{KeyVault, VaultError, VaultUnlock, PASSPHRASE_ENV};
+
+/// Store file holding persisted AI settings (inside the app data directory)
+const STORE_FILE: &str = "ai-config.json";
+
+/// Key under which the AI configuration is saved in the store
+const CONFIG_KEY: &str = "config";
+
+/// Encrypted vault file for API keys (inside the app data directory)
+const VAULT_FILE: &str = "ai-keys.vault";
+
+/// Vault entry name for the OpenAI API key
+const OPENAI_KEY_ENTRY: &str = "openai_api_key";
+
+/// Vault entry name for the Anthropic API key
+const ANTHROPIC_KEY_ENTRY: &str = "anthropic_api_key";
+
+/// CLIProxyAPI URL saved by older versions as the default, before the managed
+/// server picked its own port
+const LEGACY_CLIPROXYAPI_URL: &str = "http://localhost:8080";
+
+/// Load the persisted AI configuration, falling back to defaults
+///
+/// Missing or unreadable stores are not fatal: the app starts with
+/// `AIConfig::default()` and the user can fix settings at runtime.
+/// API keys are read from the encrypted vault, not the plaintext store.
+pub fn load_ai_config<R: Runtime>(app: &AppHandle<R>) -> AIConfig {
+    let mut config = match app.store(STORE_FILE) {
+        Ok(store) => match store.get(CONFIG_KEY) {
+            Some(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+                eprintln!("[AI] Ignoring invalid stored config: {}", e);
+                AIConfig::default()
+            }),
+            None => AIConfig::default(),
+        },
+        Err(e) => {
+            eprintln!("[AI] Failed to open config store: {}", e);
+            AIConfig::default()
+        }
+    };
+
+    if config.cliproxyapi_url.as_deref() == Some(LEGACY_CLIPROXYAPI_URL) {
+        config.cliproxyapi_url = None;
+    }
+
+    match open_vault(app) {
+        Ok(vault) => {
+            config.openai_api_key = vault.get(OPENAI_KEY_ENTRY);
+            config.anthropic_api_key = vault.get(ANTHROPIC_KEY_ENTRY);
+        }
+        // Reported to the user through `key_vault_status`
+        Err(e) => eprintln!("[AI] Failed to open key vault: {}", e),
+    }
+
+    config
+}
+
+/// Persist the AI configuration to the store and its API keys to the vault
+pub fn save_ai_config<R: Runtime>(app: &AppHandle<R>, config: &AIConfig) -> Result<(), String> {
+    let store = app
+        .store(STORE_FILE)
+        .map_err(|e| format!("Failed to open config store: {}", e))?;
+
+    store.set(CONFIG_KEY, json!(config));
+    store
+        .save()
+        .map_err(|e| format!("Failed to save config store: {}", e))?;
+
+    let mut vault = open_vault(app)?;
+    for (entry, key) in [
+        (OPENAI_KEY_ENTRY, &config.openai_api_key),
+        (ANTHROPIC_KEY_ENTRY, &config.anthropic_api_key),
+    ] {
+        match key {
+            Some(key) if !key.is_empty() => vault.set(entry, key.clone()),
+            _ => vault.remove(entry),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Problem opening the API key vault, if any
+///
+/// While the vault can't be decrypted, stored keys are unavailable and
+/// new ones can't be saved until the vault is reset.
+pub fn key_vault_status<R: Runtime>(app: &AppHandle<R>) -> Option<String> {
+    open_vault(app).err()
+}
+
+/// Move an unreadable vault aside so API keys can be entered again
+///
+/// The old file is kept as `ai-keys.vault.unreadable` in case the original
+/// passphrase or machine key turns up again.
+pub fn reset_key_vault<R: Runtime>(app: &AppHandle<R>) -> Result<(), String> {
+    let path = vault_path(app)?;
+    if path.exists() {
+        std::fs::rename(&path, path.with_extension("vault.unreadable"))
+            .map_err(|e| format!("Failed to reset key vault: {}", e))?;
+    }
+    Ok(())
+}
+
+fn vault_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {}", e))?
+        .join(VAULT_FILE))
+}
+
+/// Open the API key vault in the app data directory
+fn open_vault<R: Runtime>(app: &AppHandle<R>) -> Result<KeyVault, String> {
+    KeyVault::open(vault_path(app)?, &VaultUnlock::from_env()).map_err(|e| match e {
+        VaultError::Decrypt | VaultError::KeyMissing => format!(
+            "Stored API keys can't be decrypted ({}). Set {} to the passphrase used before, \
+             or reset the key vault and enter the keys again.",
+            e, PASSPHRASE_ENV
+        ),
+        e => e.to_string(),
+    })
+}
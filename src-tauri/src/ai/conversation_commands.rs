@@ -0,0 +1,107 @@
+use tauri::State;
+use tokio::sync::Mutex;
+
+use super::conversations::{
+    self, Conversation, ConversationStore, ConversationSummary, DEFAULT_CONTEXT_TOKENS,
+};
+use super::manager::AIProviderManager;
+use super::types::{AIResponse, ChatMessage};
+
+/// Tauri command to create a new conversation
+#[tauri::command]
+pub async fn ai_conversation_create(
+    title: Option<String>,
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<Conversation, String> {
+    let store = store.lock().await;
+    store.create(title.unwrap_or_else(|| "New conversation".to_string()))
+}
+
+/// Tauri command to list conversations (most recent first)
+#[tauri::command]
+pub async fn ai_conversation_list(
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<Vec<ConversationSummary>, String> {
+    let store = store.lock().await;
+    store.list()
+}
+
+/// Tauri command to load a conversation with its full history
+#[tauri::command]
+pub async fn ai_conversation_get(
+    id: String,
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<Conversation, String> {
+    let store = store.lock().await;
+    store.get(&id)
+}
+
+/// Tauri command to rename a conversation
+#[tauri::command]
+pub async fn ai_conversation_rename(
+    id: String,
+    title: String,
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<Conversation, String> {
+    let store = store.lock().await;
+    store.rename(&id, title)
+}
+
+/// Tauri command to delete a conversation
+#[tauri::command]
+pub async fn ai_conversation_delete(
+    id: String,
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<(), String> {
+    let store = store.lock().await;
+    store.delete(&id)
+}
+
+/// Tauri command to append messages without calling the AI
+#[tauri::command]
+pub async fn ai_conversation_append(
+    id: String,
+    messages: Vec<ChatMessage>,
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<Conversation, String> {
+    let store = store.lock().await;
+    store.append(&id, messages)
+}
+
+/// Tauri command to fork a conversation, optionally at a message index
+#[tauri::command]
+pub async fn ai_conversation_fork(
+    id: String,
+    at: Option<usize>,
+    store: State<'_, Mutex<ConversationStore>>,
+) -> Result<Conversation, String> {
+    let store = store.lock().await;
+    store.fork(&id, at)
+}
+
+/// Tauri command to send a message in a conversation and get the AI reply
+///
+/// Both the message and the reply are persisted. Long histories are
+/// trimmed to `max_context_tokens` and older turns summarized.
+#[tauri::command]
+pub async fn ai_conversation_send(
+    id: String,
+    message: ChatMessage,
+    model: Option<String>,
+    task: Option<String>,
+    max_context_tokens: Option<usize>,
+    store: State<'_, Mutex<ConversationStore>>,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<AIResponse, String> {
+    let manager = state.lock().await.clone();
+    conversations::send_message(
+        &store,
+        &manager,
+        &id,
+        message,
+        model,
+        task.as_deref(),
+        max_context_tokens.unwrap_or(DEFAULT_CONTEXT_TOKENS),
+    )
+    .await
+}
@@ -0,0 +1,492 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+use super::manager::AIProviderManager;
+use super::storage::{new_id, now_millis, read_json, write_json};
+use super::types::{AIResponse, ChatMessage, GenerationOptions};
+
+/// Rough characters-per-token ratio used for context budgeting
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Default context budget when the caller doesn't specify one
+pub const DEFAULT_CONTEXT_TOKENS: usize = 16_000;
+
+/// Routing task used for summarization requests
+const SUMMARY_TASK: &str = "summarize";
+
+/// A persisted chat thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    /// Creation time (Unix millis)
+    pub created_at: u64,
+    /// Last modification time (Unix millis)
+    pub updated_at: u64,
+    /// Conversation this one was forked from
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Full message history
+    pub messages: Vec<ChatMessage>,
+    /// Summary of the first `summarized_count` non-system messages, used
+    /// once they no longer fit in the context window
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub summarized_count: usize,
+}
+
+/// Conversation metadata for listings
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub parent_id: Option<String>,
+    pub message_count: usize,
+}
+
+/// Messages to send for a request, after fitting the context budget
+pub struct ContextWindow {
+    pub messages: Vec<ChatMessage>,
+    /// Non-system messages dropped from the front of the history
+    pub dropped: usize,
+}
+
+/// File-backed conversation store (one JSON file per conversation)
+pub struct ConversationStore {
+    dir: PathBuf,
+}
+
+impl ConversationStore {
+    /// Create a store rooted at `dir` (created on first write)
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, String> {
+        // IDs are generated hex strings; reject anything that could escape the dir
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("Invalid conversation id: {}", id));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    /// Create an empty conversation
+    pub fn create(&self, title: String) -> Result<Conversation, String> {
+        let now = now_millis();
+        let conversation = Conversation {
+            id: new_id(),
+            title,
+            created_at: now,
+            updated_at: now,
+            parent_id: None,
+            messages: Vec::new(),
+            summary: None,
+            summarized_count: 0,
+        };
+        self.save(&conversation)?;
+        Ok(conversation)
+    }
+
+    /// List conversations, most recently updated first
+    pub fn list(&self) -> Result<Vec<ConversationSummary>, String> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let entries = std::fs::read_dir(&self.dir)
+            .map_err(|e| format!("Failed to read conversations: {}", e))?;
+
+        let mut summaries = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match read_json::<Conversation>(&path) {
+                Ok(Some(c)) => summaries.push(ConversationSummary {
+                    id: c.id,
+                    title: c.title,
+                    created_at: c.created_at,
+                    updated_at: c.updated_at,
+                    parent_id: c.parent_id,
+                    message_count: c.messages.len(),
+                }),
+                Ok(None) => {}
+                Err(e) => eprintln!("[AI] Skipping conversation file: {}", e),
+            }
+        }
+
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(summaries)
+    }
+
+    /// Load a conversation by id
+    pub fn get(&self, id: &str) -> Result<Conversation, String> {
+        read_json(&self.path(id)?)?.ok_or_else(|| format!("Conversation '{}' not found", id))
+    }
+
+    /// Write a conversation to disk
+    pub fn save(&self, conversation: &Conversation) -> Result<(), String> {
+        write_json(&self.path(&conversation.id)?, conversation)
+    }
+
+    /// Rename a conversation
+    pub fn rename(&self, id: &str, title: String) -> Result<Conversation, String> {
+        let mut conversation = self.get(id)?;
+        conversation.title = title;
+        conversation.updated_at = now_millis();
+        self.save(&conversation)?;
+        Ok(conversation)
+    }
+
+    /// Delete a conversation
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            return Err(format!("Conversation '{}' not found", id));
+        }
+        std::fs::remove_file(path).map_err(|e| format!("Failed to delete conversation: {}", e))
+    }
+
+    /// Append messages to a conversation
+    pub fn append(&self, id: &str, messages: Vec<ChatMessage>) -> Result<Conversation, String> {
+        let mut conversation = self.get(id)?;
+        conversation.messages.extend(messages);
+        conversation.updated_at = now_millis();
+        self.save(&conversation)?;
+        Ok(conversation)
+    }
+
+    /// Copy a conversation into a new thread, optionally keeping only the
+    /// first `at` messages (to branch from an earlier point)
+    pub fn fork(&self, id: &str, at: Option<usize>) -> Result<Conversation, String> {
+        let source = self.get(id)?;
+        let keep = at.unwrap_or(source.messages.len()).min(source.messages.len());
+        let kept_history = source.messages[..keep]
+            .iter()
+            .filter(|m| m.role != "system")
+            .count();
+
+        // The summary only stays valid if it covers messages we keep
+        let (summary, summarized_count) = if source.summarized_count <= kept_history {
+            (source.summary.clone(), source.summarized_count)
+        } else {
+            (None, 0)
+        };
+
+        let now = now_millis();
+        let fork = Conversation {
+            id: new_id(),
+            title: format!("{} (fork)", source.title),
+            created_at: now,
+            updated_at: now,
+            parent_id: Some(source.id.clone()),
+            messages: source.messages[..keep].to_vec(),
+            summary,
+            summarized_count,
+        };
+        self.save(&fork)?;
+        Ok(fork)
+    }
+}
+
+/// Estimate the token count of a message
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    // Per-message overhead for role and formatting
+    4 + message.content.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Fit a conversation into `max_tokens`
+///
+/// System messages are always kept. The most recent other messages are
+/// kept until the budget runs out; if older ones are dropped and a summary
+/// covering them exists, it is inserted as a system message.
+pub fn build_context(conversation: &Conversation, max_tokens: usize) -> ContextWindow {
+    let (system, history): (Vec<&ChatMessage>, Vec<&ChatMessage>) = conversation
+        .messages
+        .iter()
+        .partition(|m| m.role == "system");
+
+    let summary_message = conversation.summary.as_ref().map(|summary| ChatMessage {
+        role: "system".to_string(),
+        content: format!("Summary of the earlier conversation:\n{}", summary),
+    });
+
+    let mut budget = max_tokens.saturating_sub(system.iter().map(|m| estimate_tokens(m)).sum());
+    if let Some(summary) = &summary_message {
+        budget = budget.saturating_sub(estimate_tokens(summary));
+    }
+
+    // Walk back from the newest message, always keeping at least one
+    let mut keep_from = history.len();
+    for (i, message) in history.iter().enumerate().rev() {
+        let cost = estimate_tokens(message);
+        if cost > budget && keep_from < history.len() {
+            break;
+        }
+        budget = budget.saturating_sub(cost);
+        keep_from = i;
+    }
+
+    let mut messages: Vec<ChatMessage> = system.into_iter().cloned().collect();
+    if keep_from > 0 {
+        if let Some(summary) = summary_message {
+            messages.push(summary);
+        }
+    }
+    messages.extend(history[keep_from..].iter().map(|m| (*m).clone()));
+
+    ContextWindow {
+        messages,
+        dropped: keep_from,
+    }
+}
+
+/// Send a user message in a conversation and store the reply
+///
+/// When older messages no longer fit in `max_tokens`, they are summarized
+/// (incrementally, on top of any existing summary) before the request.
+/// The store is only locked to load and save, not while providers answer.
+pub async fn send_message(
+    store: &Mutex<ConversationStore>,
+    manager: &AIProviderManager,
+    id: &str,
+    message: ChatMessage,
+    model: Option<String>,
+    task: Option<&str>,
+    max_tokens: usize,
+) -> Result<AIResponse, String> {
+    let mut conversation = store.lock().await.get(id)?;
+    conversation.messages.push(message.clone());
+
+    let mut window = build_context(&conversation, max_tokens);
+    // The summary takes budget too and may push out more messages, so repeat
+    // until it covers everything dropped (`summarized_count` only grows)
+    while window.dropped > conversation.summarized_count {
+        match summarize(manager, &conversation, window.dropped).await {
+            Ok(summary) => {
+                conversation.summary = Some(summary);
+                conversation.summarized_count = window.dropped;
+                window = build_context(&conversation, max_tokens);
+            }
+            // Not fatal: continue with the trimmed context and retry next turn
+            Err(e) => {
+                eprintln!("[AI] Conversation summary failed: {}", e);
+                break;
+            }
+        }
+    }
+
+    let response = manager
+        .complete_for_task(window.messages, model, &GenerationOptions::default(), task)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Reload, as messages may have been added while waiting for the reply
+    let store = store.lock().await;
+    let mut saved = store.get(id)?;
+    saved.messages.push(message);
+    saved.messages.push(ChatMessage {
+        role: "assistant".to_string(),
+        content: response.content.clone(),
+    });
+    // Appending keeps the summarized prefix intact, so the new summary still applies
+    if conversation.summarized_count > saved.summarized_count {
+        saved.summary = conversation.summary;
+        saved.summarized_count = conversation.summarized_count;
+    }
+    saved.updated_at = now_millis();
+    store.save(&saved)?;
+
+    Ok(response)
+}
+
+/// Summarize the first `upto` non-system messages, extending the current summary
+async fn summarize(
+    manager: &AIProviderManager,
+    conversation: &Conversation,
+    upto: usize,
+) -> Result<String, String> {
+    let history: Vec<&ChatMessage> = conversation
+        .messages
+        .iter()
+        .filter(|m| m.role != "system")
+        .collect();
+
+    let mut transcript = String::new();
+    if let Some(summary) = &conversation.summary {
+        transcript.push_str(&format!("Existing summary:\n{}\n\nNew messages:\n", summary));
+    }
+    for message in &history[conversation.summarized_count..upto] {
+        transcript.push_str(&format!("{}: {}\n", message.role, message.content));
+    }
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: "Summarize this songwriting conversation so it can replace the original \
+                      messages. Keep decisions, lyrics fragments, keys, tempos and open \
+                      questions. Be concise."
+                .to_string(),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ];
+
+    let options = GenerationOptions {
+        temperature: Some(0.3),
+        ..GenerationOptions::default()
+    };
+
+    manager
+        .complete_for_task(messages, None, &options, Some(SUMMARY_TASK))
+        .await
+        .map(|r| r.content)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::routing::RoutingPolicy;
+    use crate::ai::testing::{MockProvider, TempDir};
+    use std::time::Duration;
+
+    /// 40 characters: 14 tokens per message
+    const TEXT: &str = "a line of songwriting chat, forty chars.";
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    /// Alternating user/assistant history of `count` messages
+    fn history(count: usize) -> Vec<ChatMessage> {
+        (0..count)
+            .map(|i| message(if i % 2 == 0 { "user" } else { "assistant" }, TEXT))
+            .collect()
+    }
+
+    fn temp_store() -> (ConversationStore, TempDir) {
+        let dir = TempDir::new("conversations");
+        (ConversationStore::new(dir.path()), dir)
+    }
+
+    fn stored(store: &ConversationStore, messages: Vec<ChatMessage>) -> Conversation {
+        let conversation = store.create("Verse ideas".to_string()).unwrap();
+        store.append(&conversation.id, messages).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_newest_messages_that_fit() {
+        let (store, _dir) = temp_store();
+        let conversation = stored(&store, [vec![message("system", "s")], history(10)].concat());
+        let system_cost = estimate_tokens(&conversation.messages[0]);
+        assert_eq!(estimate_tokens(&conversation.messages[1]), 14);
+
+        let window = build_context(&conversation, system_cost + 3 * 14);
+        assert_eq!(window.dropped, 7);
+        assert_eq!(window.messages.len(), 4);
+        assert_eq!(window.messages[0].role, "system");
+
+        // The newest message is sent even when nothing fits
+        let window = build_context(&conversation, 0);
+        assert_eq!(window.dropped, 9);
+        assert_eq!(window.messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn summarizes_everything_the_window_drops() {
+        let (store, _dir) = temp_store();
+        let conversation = stored(&store, history(6));
+        let a = MockProvider::new("a").reply("earlier").reply("earlier, extended").reply("reply");
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+        let store = Mutex::new(store);
+
+        let response = send_message(&store, &manager, &conversation.id, message("user", TEXT), None, None, 3 * 14)
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "reply");
+        let saved = store.lock().await.get(&conversation.id).unwrap();
+        assert_eq!(saved.messages.len(), 8);
+        assert_eq!(saved.summary.as_deref(), Some("earlier, extended"));
+        // The first summary left room for only the newest message, so it was extended
+        assert_eq!(saved.summarized_count, 6);
+        assert_eq!(a.calls(), 3);
+
+        let sent = a.last_messages().unwrap();
+        assert!(sent[0].content.ends_with("earlier, extended"));
+        assert_eq!(sent.last().unwrap().content, TEXT);
+    }
+
+    #[tokio::test]
+    async fn reuses_a_summary_that_still_covers_the_dropped_messages() {
+        let (store, _dir) = temp_store();
+        let mut conversation = stored(&store, history(6));
+        conversation.summary = Some("earlier".to_string());
+        conversation.summarized_count = 6;
+        store.save(&conversation).unwrap();
+        let a = MockProvider::new("a").reply("reply");
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+        let store = Mutex::new(store);
+
+        send_message(&store, &manager, &conversation.id, message("user", TEXT), None, None, 3 * 14)
+            .await
+            .unwrap();
+
+        assert_eq!(a.calls(), 1, "no new summary request");
+        assert_eq!(a.last_messages().unwrap()[0].content, "Summary of the earlier conversation:\nearlier");
+        assert_eq!(store.lock().await.get(&conversation.id).unwrap().summarized_count, 6);
+    }
+
+    #[tokio::test]
+    async fn the_store_stays_usable_while_waiting_for_the_reply() {
+        let (store, _dir) = temp_store();
+        let conversation = stored(&store, history(2));
+        let a = MockProvider::new("a").delayed(Duration::from_millis(200)).reply("reply");
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+        let store = Mutex::new(store);
+
+        let send = send_message(&store, &manager, &conversation.id, message("user", "new"), None, None, 1_000);
+        let meanwhile = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let store = store.try_lock().expect("store is not locked during the provider call");
+            store.append(&conversation.id, vec![message("user", "meanwhile")]).unwrap();
+        };
+        let (response, _) = tokio::join!(send, meanwhile);
+        response.unwrap();
+
+        let saved = store.lock().await.get(&conversation.id).unwrap();
+        let contents: Vec<&str> = saved.messages[2..].iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["meanwhile", "new", "reply"]);
+    }
+
+    #[test]
+    fn forks_keep_the_summary_only_while_it_still_applies() {
+        let (store, _dir) = temp_store();
+        let mut source = stored(&store, history(4));
+        source.summary = Some("earlier".to_string());
+        source.summarized_count = 2;
+        store.save(&source).unwrap();
+
+        let full = store.fork(&source.id, None).unwrap();
+        assert_eq!(full.parent_id.as_deref(), Some(source.id.as_str()));
+        assert_eq!(full.messages.len(), 4);
+        assert_eq!((full.summary.as_deref(), full.summarized_count), (Some("earlier"), 2));
+
+        let early = store.fork(&source.id, Some(1)).unwrap();
+        assert_eq!(early.messages.len(), 1);
+        assert_eq!((early.summary, early.summarized_count), (None, 0));
+
+        assert_eq!(store.list().unwrap().len(), 3);
+    }
+}
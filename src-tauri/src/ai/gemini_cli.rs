@@ -1,17 +1,31 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
 
 use super::provider::{AIError, AIProvider};
-use super::types::{AIResponse, ChatMessage};
+use super::secret::SecretString;
+use super::types::{AIResponse, ChatMessage, TokenUsage};
 
 /// Gemini CLI cached credentials
 #[derive(Debug, Deserialize)]
 struct GeminiCredentials {
-    access_token: Option<String>,
-    refresh_token: Option<String>,
-    expiry: Option<String>,
+    access_token: Option<SecretString>,
+}
+
+/// Credential used to authenticate Gemini requests
+enum GeminiAuth {
+    /// API key, sent in the `x-goog-api-key` header
+    ApiKey(SecretString),
+    /// OAuth access token from Gemini CLI, sent as a bearer token
+    OAuth(SecretString),
+}
+
+impl GeminiAuth {
+    fn secret(&self) -> &SecretString {
+        match self {
+            GeminiAuth::ApiKey(key) | GeminiAuth::OAuth(key) => key,
+        }
+    }
 }
 
 /// Request body for Gemini API
@@ -65,6 +79,10 @@ struct GeminiPartResponse {
 
 #[derive(Debug, Deserialize)]
 struct UsageMetadata {
+    #[serde(rename = "promptTokenCount")]
+    prompt_token_count: Option<u32>,
+    #[serde(rename = "candidatesTokenCount")]
+    candidates_token_count: Option<u32>,
     #[serde(rename = "totalTokenCount")]
     total_token_count: Option<u32>,
 }
@@ -72,25 +90,27 @@ struct UsageMetadata {
 /// Gemini CLI provider using OAuth tokens or API key
 pub struct GeminiCLIProvider {
     client: Client,
+    base_url: String,
     model: String,
-    api_key: Option<String>,
+    api_key: Option<SecretString>,
 }
 
 impl GeminiCLIProvider {
     /// Create a new Gemini CLI provider
     /// Tries to use GEMINI_API_KEY env var, or falls back to cached OAuth
-    pub fn new(api_key: Option<String>, model: Option<String>) -> Self {
-        let api_key = api_key.or_else(|| std::env::var("GEMINI_API_KEY").ok());
+    pub fn new(api_key: Option<SecretString>, model: Option<String>) -> Self {
+        let api_key = api_key.or_else(|| std::env::var("GEMINI_API_KEY").ok().map(SecretString::new));
 
         Self {
             client: Client::new(),
+            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
             model: model.unwrap_or_else(|| "gemini-2.0-flash".to_string()),
             api_key,
         }
     }
 
     /// Try to load OAuth tokens from Gemini CLI cache
-    fn load_oauth_tokens(&self) -> Option<String> {
+    fn load_oauth_tokens(&self) -> Option<SecretString> {
         // Gemini CLI stores tokens in application-specific location
         let home = dirs::home_dir()?;
 
@@ -115,7 +135,8 @@ impl GeminiCLIProvider {
 
     fn get_endpoint(&self) -> String {
         format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
+            "{}/models/{}:generateContent",
+            self.base_url.trim_end_matches('/'),
             self.model
         )
     }
@@ -126,7 +147,8 @@ impl AIProvider for GeminiCLIProvider {
     async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
         // Try API key first, then OAuth
         let auth = self.api_key.clone()
-            .or_else(|| self.load_oauth_tokens())
+            .map(GeminiAuth::ApiKey)
+            .or_else(|| self.load_oauth_tokens().map(GeminiAuth::OAuth))
             .ok_or_else(|| AIError::InvalidConfig(
                 "No Gemini API key or OAuth tokens. Set GEMINI_API_KEY or login via 'gemini' CLI.".to_string()
             ))?;
@@ -148,42 +170,47 @@ impl AIProvider for GeminiCLIProvider {
             }),
         };
 
-        let url = format!("{}?key={}", self.get_endpoint(), auth);
+        // Credentials go in headers, never the URL, so they can't end up in logs
+        let builder = self.client.post(self.get_endpoint());
+        let builder = match &auth {
+            GeminiAuth::ApiKey(key) => builder.header("x-goog-api-key", key.expose()),
+            GeminiAuth::OAuth(token) => builder.bearer_auth(token.expose()),
+        };
 
-        let response = self
-            .client
-            .post(&url)
+        let response = builder
             .header("content-type", "application/json")
             .json(&request)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AIError::ApiError(format!(
-                "Gemini API error ({}): {}",
-                status, error_text
-            )));
+            return Err(AIError::from_response("Gemini API", response, Some(auth.secret())).await);
         }
 
         let completion: GeminiResponse = response.json().await?;
 
         let content = completion
             .candidates
-            .and_then(|c| c.first().cloned())
+            .and_then(|c| c.into_iter().next())
             .and_then(|c| c.content)
             .and_then(|c| c.parts)
-            .and_then(|p| p.first().cloned())
+            .and_then(|p| p.into_iter().next())
             .and_then(|p| p.text)
             .unwrap_or_default();
 
-        let tokens_used = completion.usage_metadata.and_then(|u| u.total_token_count);
+        let tokens_used = completion.usage_metadata.as_ref().and_then(|u| u.total_token_count);
+        let usage = completion.usage_metadata.as_ref().map(|u| TokenUsage {
+            prompt_tokens: u.prompt_token_count.unwrap_or(0),
+            completion_tokens: u.candidates_token_count.unwrap_or(0),
+        });
 
         Ok(AIResponse {
             content,
             provider: "gemini".to_string(),
             tokens: tokens_used,
+            model: Some(self.model.clone()),
+            usage,
+            route: None,
         })
     }
 
@@ -191,7 +218,58 @@ impl AIProvider for GeminiCLIProvider {
         "gemini"
     }
 
+    fn configured_model(&self) -> Option<String> {
+        Some(self.model.clone())
+    }
+
     async fn is_available(&self) -> bool {
         self.api_key.is_some() || self.load_oauth_tokens().is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{gemini_generate, user_message, StubResponse, StubServer};
+    use serde_json::json;
+
+    const KEY: &str = "AIza-test-secret-key";
+    const PATH: &str = "/models/gemini-2.0-flash:generateContent";
+
+    fn provider(server: &StubServer) -> GeminiCLIProvider {
+        let mut provider = GeminiCLIProvider::new(Some(SecretString::new(KEY)), None);
+        provider.base_url = server.url().to_string();
+        provider
+    }
+
+    #[tokio::test]
+    async fn sends_the_api_key_in_a_header() {
+        let server = StubServer::start().await;
+        server.on("POST", PATH, StubResponse::json(200, gemini_generate("Am")));
+
+        let response = provider(&server).complete(user_message("relative minor of C?")).await.unwrap();
+
+        assert_eq!(response.content, "Am");
+        assert_eq!(response.tokens, Some(20));
+        assert_eq!(response.usage.map(|u| u.completion_tokens), Some(8));
+
+        let request = &server.requests_to(PATH)[0];
+        assert_eq!(request.headers["x-goog-api-key"], KEY);
+        assert!(!request.path.contains(KEY));
+    }
+
+    #[tokio::test]
+    async fn error_messages_redact_the_api_key() {
+        let server = StubServer::start().await;
+        server.on(
+            "POST",
+            PATH,
+            StubResponse::json(400, json!({ "error": { "message": format!("API key not valid: {}", KEY) } })),
+        );
+
+        let error = provider(&server).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Http { status: 400, .. }));
+        assert!(!error.to_string().contains(KEY));
+    }
+}
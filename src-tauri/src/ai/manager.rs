@@ -1,35 +1,206 @@
-use super::cliproxyapi::CLIProxyAPIProvider;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+
+use super::anthropic::AnthropicProvider;
+use super::cache::ResponseCache;
+use super::compare::{CompareResult, CompareTarget};
+use super::cliproxyapi::{CLIProxyAPIProvider, ServerUrl};
 use super::openai::OpenAIProvider;
-use super::provider::{AIError, AIProvider};
-use super::types::{AIConfig, AIResponse, ChatMessage};
+use super::provider::{AIError, AIProvider, ProviderDiagnostics};
+use super::retry::{CircuitBreaker, RetryPolicy};
+use super::routing::{LatencyTracker, RouteInfo};
+use super::types::{AIConfig, AIResponse, ChatMessage, GenerationOptions, ModelInfo};
+use super::usage::UsageLedger;
 
 /// Manager for multiple AI providers with automatic routing
+///
+/// Clones are cheap and share providers, circuit breakers and latency
+/// stats, so commands clone it out of the app state lock and make their
+/// (possibly slow, retried) provider calls without holding it.
+#[derive(Clone)]
 pub struct AIProviderManager {
-    providers: Vec<Box<dyn AIProvider>>,
+    providers: Arc<Vec<Box<dyn AIProvider>>>,
     default_provider: String,
+    config: AIConfig,
+    retry_policy: RetryPolicy,
+    breaker: Arc<CircuitBreaker>,
+    latencies: Arc<LatencyTracker>,
+    usage: Option<Arc<UsageLedger>>,
+    cache: Option<Arc<ResponseCache>>,
+    /// URL of the managed CLIProxyAPI server, used unless the config names one
+    cliproxyapi_url: Option<ServerUrl>,
 }
 
 impl AIProviderManager {
     /// Create a new AI provider manager with the given configuration
     pub fn new(config: AIConfig) -> Self {
+        Self {
+            providers: Arc::new(Self::build_providers(&config, None)),
+            default_provider: config.default_provider.clone(),
+            config,
+            retry_policy: RetryPolicy::default(),
+            breaker: Arc::default(),
+            latencies: Arc::default(),
+            usage: None,
+            cache: None,
+            cliproxyapi_url: None,
+        }
+    }
+
+    /// Build the provider list for a configuration
+    fn build_providers(config: &AIConfig, managed_url: Option<&ServerUrl>) -> Vec<Box<dyn AIProvider>> {
         let mut providers: Vec<Box<dyn AIProvider>> = Vec::new();
 
         // Add CLIProxyAPI provider (primary - uses Claude/Gemini/Codex via OAuth)
-        let cliproxyapi = CLIProxyAPIProvider::new(config.cliproxyapi_url, config.cliproxyapi_model);
+        let external_url = config.cliproxyapi_url.clone().filter(|url| !url.is_empty());
+        let cliproxyapi = match (external_url, managed_url) {
+            (None, Some(url)) => {
+                CLIProxyAPIProvider::with_server_url(url.clone(), config.cliproxyapi_model.clone())
+            }
+            (url, _) => CLIProxyAPIProvider::new(url, config.cliproxyapi_model.clone()),
+        };
         providers.push(Box::new(cliproxyapi));
 
         // Add OpenAI provider if API key is provided (fallback)
-        if let Some(api_key) = config.openai_api_key {
+        if let Some(api_key) = &config.openai_api_key {
             if !api_key.is_empty() {
-                if let Ok(openai) = OpenAIProvider::new(api_key, config.openai_model) {
+                if let Ok(openai) = OpenAIProvider::new(api_key.clone(), config.openai_model.clone()) {
                     providers.push(Box::new(openai));
                 }
             }
         }
 
-        Self {
-            providers,
-            default_provider: config.default_provider,
+        // Add Anthropic provider if API key is provided
+        if let Some(api_key) = &config.anthropic_api_key {
+            if !api_key.is_empty() {
+                if let Ok(anthropic) = AnthropicProvider::new(api_key.clone(), config.anthropic_model.clone()) {
+                    providers.push(Box::new(anthropic));
+                }
+            }
+        }
+
+        providers
+    }
+
+    /// Get the current configuration
+    pub fn config(&self) -> &AIConfig {
+        &self.config
+    }
+
+    /// Replace the configuration and rebuild all providers
+    pub fn update_config(&mut self, config: AIConfig) {
+        self.providers = Arc::new(Self::build_providers(&config, self.cliproxyapi_url.as_ref()));
+        self.default_provider = config.default_provider.clone();
+        self.config = config;
+        self.breaker.reset();
+
+        // Cached replies may come from a model or provider no longer configured
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.clear() {
+                eprintln!("[AI] {}", e);
+            }
+        }
+    }
+
+    /// Talk to the managed CLIProxyAPI server (unless the config names another URL)
+    pub fn set_cliproxyapi_url(&mut self, url: ServerUrl) {
+        self.cliproxyapi_url = Some(url);
+        self.providers = Arc::new(Self::build_providers(&self.config, self.cliproxyapi_url.as_ref()));
+    }
+
+    /// Record every provider call in the given usage ledger
+    pub fn set_usage_ledger(&mut self, ledger: Arc<UsageLedger>) {
+        self.usage = Some(ledger);
+    }
+
+    /// Serve repeated requests from the given response cache (if enabled in config)
+    pub fn set_response_cache(&mut self, cache: Arc<ResponseCache>) {
+        self.cache = Some(cache);
+    }
+
+    /// Record a provider call in the usage ledger, if one is attached
+    fn record_usage(
+        &self,
+        provider: &dyn AIProvider,
+        model: &Option<String>,
+        result: &Result<AIResponse, AIError>,
+        started: Instant,
+    ) {
+        let Some(ledger) = &self.usage else {
+            return;
+        };
+
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(response) => ledger.record(
+                provider.name(),
+                response.model.clone().or_else(|| model.clone()),
+                response.usage,
+                response.tokens,
+                latency_ms,
+                true,
+            ),
+            Err(_) => ledger.record(provider.name(), model.clone(), None, None, latency_ms, false),
+        }
+    }
+
+    /// Call a provider, retrying transient errors with backoff
+    ///
+    /// Outcomes are reported to the circuit breaker once retries are exhausted;
+    /// only errors that count as provider failures trip it.
+    async fn call_with_retry(
+        &self,
+        provider: &dyn AIProvider,
+        messages: &[ChatMessage],
+        model: Option<String>,
+        options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        let mut attempt = 1;
+
+        loop {
+            let started = Instant::now();
+            let result = provider
+                .complete_with_options(messages.to_vec(), model.clone(), options)
+                .await;
+
+            self.record_usage(provider, &model, &result, started);
+
+            let error = match result {
+                Ok(response) => {
+                    self.breaker.record_success(provider.name());
+                    return Ok(response);
+                }
+                Err(e) => e,
+            };
+
+            let delay = if error.is_retryable() && attempt < self.retry_policy.max_attempts {
+                self.retry_policy.delay(attempt, error.retry_after())
+            } else {
+                None
+            };
+
+            match delay {
+                Some(delay) => {
+                    eprintln!(
+                        "[AI] {} attempt {} failed ({}), retrying in {:?}",
+                        provider.name(),
+                        attempt,
+                        error,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => {
+                    if error.counts_as_provider_failure() {
+                        self.breaker.record_failure(provider.name());
+                    }
+                    return Err(error);
+                }
+            }
         }
     }
 
@@ -39,41 +210,95 @@ impl AIProviderManager {
         messages: Vec<ChatMessage>,
         model: Option<String>,
     ) -> Result<AIResponse, AIError> {
-        // Build provider order: default first, then others
-        let mut provider_order: Vec<&Box<dyn AIProvider>> = Vec::new();
+        self.complete_for_task(messages, model, &GenerationOptions::default(), None)
+            .await
+    }
 
-        // Add default provider first if it exists
-        if let Some(default) = self.providers.iter().find(|p| p.name() == self.default_provider) {
-            provider_order.push(default);
-        }
+    /// Generate a completion with model override, generation options and auto-fallback
+    pub async fn complete_with_options(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        self.complete_for_task(messages, model, options, None).await
+    }
 
-        // Add remaining providers
-        for provider in &self.providers {
-            if provider.name() != self.default_provider {
-                provider_order.push(provider);
-            }
-        }
+    /// Generate a completion with auto-fallback to other providers on failure
+    ///
+    /// Tries providers in the order given by the routing policy.
+    /// Returns the first successful response or the last error if all fail.
+    pub async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
+        self.complete_for_task(messages, None, &GenerationOptions::default(), None)
+            .await
+    }
 
-        if provider_order.is_empty() {
-            return Err(AIError::ProviderUnavailable("No providers configured".to_string()));
-        }
+    /// Generate a completion routed by task (e.g., "lyrics", "theory")
+    ///
+    /// This is the single code path behind all completion methods. The
+    /// chosen route is reported in `AIResponse::route`. `response_format`
+    /// is only forwarded to providers that support structured output.
+    ///
+    /// With caching enabled, a fresh cached response for a candidate is
+    /// returned before that provider is checked or called, so repeated
+    /// requests also work offline.
+    pub async fn complete_for_task(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+        task: Option<&str>,
+    ) -> Result<AIResponse, AIError> {
+        let candidates = self.route(model.as_deref(), task).await?;
+
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|_| self.config.cache.enabled && !options.bypass_cache);
 
         let mut last_error: Option<AIError> = None;
         let mut tried_providers: Vec<String> = Vec::new();
 
         // Try each provider with auto-fallback
-        for provider in provider_order {
-            let provider_name = provider.name().to_string();
+        for provider_name in &candidates {
+            let Some(provider) = self.providers.iter().find(|p| p.name() == provider_name) else {
+                continue;
+            };
+
+            let provider_options = Self::provider_options(provider.as_ref(), options);
+
+            let cache_key = cache.map(|_| {
+                let resolved_model = model.clone().or_else(|| provider.configured_model());
+                ResponseCache::key(provider_name, resolved_model.as_deref(), &messages, &provider_options)
+            });
+            if let Some((cache, key)) = cache.zip(cache_key.as_ref()) {
+                if let Some(mut response) = cache.get(key, &self.config.cache) {
+                    response.route = Some(RouteInfo {
+                        task: task.map(str::to_string),
+                        candidates,
+                        skipped: tried_providers,
+                        cached: true,
+                    });
+                    return Ok(response);
+                }
+            }
 
-            // Check availability first
-            if !provider.is_available().await {
-                tried_providers.push(format!("{} (unavailable)", provider_name));
+            if let Some(reason) = self.skip_reason(provider.as_ref(), model.as_deref()).await {
+                tried_providers.push(format!("{} ({})", provider_name, reason));
                 continue;
             }
 
             // Attempt completion with model override
-            match provider.complete_with_model(messages.clone(), model.clone()).await {
-                Ok(response) => {
+            let started = Instant::now();
+            match self
+                .call_with_retry(provider.as_ref(), &messages, model.clone(), &provider_options)
+                .await
+            {
+                Ok(mut response) => {
+                    self.latencies.record(provider_name, started.elapsed());
+                    if let Some((cache, key)) = cache.zip(cache_key.as_ref()) {
+                        cache.put(key, &response, &self.config.cache);
+                    }
                     if !tried_providers.is_empty() {
                         eprintln!(
                             "[AI] Fallback success: {} (tried: {})",
@@ -81,6 +306,12 @@ impl AIProviderManager {
                             tried_providers.join(" → ")
                         );
                     }
+                    response.route = Some(RouteInfo {
+                        task: task.map(str::to_string),
+                        candidates,
+                        skipped: tried_providers,
+                        cached: false,
+                    });
                     return Ok(response);
                 }
                 Err(e) => {
@@ -99,64 +330,77 @@ impl AIProviderManager {
         }))
     }
 
-    /// Generate a completion with auto-fallback to other providers on failure
+    /// Stream a completion routed by task, passing text to `on_delta`
     ///
-    /// Tries providers in order: default first, then others by availability.
-    /// Returns the first successful response or the last error if all fail.
-    pub async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
-        // Build provider order: default first, then others
-        let mut provider_order: Vec<&Box<dyn AIProvider>> = Vec::new();
-
-        // Add default provider first if it exists
-        if let Some(default) = self.providers.iter().find(|p| p.name() == self.default_provider) {
-            provider_order.push(default);
-        }
-
-        // Add remaining providers
-        for provider in &self.providers {
-            if provider.name() != self.default_provider {
-                provider_order.push(provider);
-            }
-        }
-
-        if provider_order.is_empty() {
-            return Err(AIError::ProviderUnavailable("No providers configured".to_string()));
-        }
+    /// Follows the same route as `complete_for_task`, but without retries
+    /// or the response cache. Falls back to the next provider only while
+    /// nothing has been streamed yet.
+    pub async fn complete_stream_for_task(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+        task: Option<&str>,
+        on_delta: &(dyn for<'a> Fn(&'a str) + Send + Sync),
+    ) -> Result<AIResponse, AIError> {
+        let candidates = self.route(model.as_deref(), task).await?;
 
         let mut last_error: Option<AIError> = None;
         let mut tried_providers: Vec<String> = Vec::new();
 
-        // Try each provider with auto-fallback
-        for provider in provider_order {
-            let provider_name = provider.name().to_string();
+        for provider_name in &candidates {
+            let Some(provider) = self.providers.iter().find(|p| p.name() == provider_name) else {
+                continue;
+            };
 
-            // Check availability first
-            if !provider.is_available().await {
-                tried_providers.push(format!("{} (unavailable)", provider_name));
+            if let Some(reason) = self.skip_reason(provider.as_ref(), model.as_deref()).await {
+                tried_providers.push(format!("{} ({})", provider_name, reason));
                 continue;
             }
 
-            // Attempt completion
-            match provider.complete(messages.clone()).await {
-                Ok(response) => {
-                    // Log fallback if we didn't use default
-                    if !tried_providers.is_empty() {
-                        eprintln!(
-                            "[AI] Fallback success: {} (tried: {})",
-                            provider_name,
-                            tried_providers.join(" → ")
-                        );
-                    }
+            let streamed = AtomicBool::new(false);
+            let forward = |text: &str| {
+                streamed.store(true, Ordering::Relaxed);
+                on_delta(text);
+            };
+
+            let started = Instant::now();
+            let result = provider
+                .complete_stream(
+                    messages.clone(),
+                    model.clone(),
+                    &Self::provider_options(provider.as_ref(), options),
+                    &forward,
+                )
+                .await;
+            self.record_usage(provider.as_ref(), &model, &result, started);
+
+            match result {
+                Ok(mut response) => {
+                    self.breaker.record_success(provider_name);
+                    self.latencies.record(provider_name, started.elapsed());
+                    response.route = Some(RouteInfo {
+                        task: task.map(str::to_string),
+                        candidates,
+                        skipped: tried_providers,
+                        cached: false,
+                    });
                     return Ok(response);
                 }
                 Err(e) => {
+                    if e.counts_as_provider_failure() {
+                        self.breaker.record_failure(provider_name);
+                    }
+                    // Partial output was already delivered; don't mix in another provider
+                    if streamed.load(Ordering::Relaxed) {
+                        return Err(e);
+                    }
                     tried_providers.push(format!("{} ({})", provider_name, e));
                     last_error = Some(e);
                 }
             }
         }
 
-        // All providers failed
         Err(last_error.unwrap_or_else(|| {
             AIError::ProviderUnavailable(format!(
                 "All providers failed: {}",
@@ -165,6 +409,171 @@ impl AIProviderManager {
         }))
     }
 
+    /// Send the same request to several providers/models concurrently
+    ///
+    /// Each target is called once (no retry, fallback or cache) and gets
+    /// `timeout` to answer. Results come back in target order, with
+    /// per-target errors instead of failing the whole comparison. Calls
+    /// are recorded in the usage ledger but don't trip circuit breakers.
+    pub async fn compare(
+        &self,
+        messages: Vec<ChatMessage>,
+        targets: &[CompareTarget],
+        options: &GenerationOptions,
+        timeout: Duration,
+    ) -> Vec<CompareResult> {
+        let calls = targets.iter().map(|target| async {
+            let failed = |error: String, timed_out: bool, latency_ms: u64| CompareResult {
+                provider: target.provider.clone(),
+                model: target.model.clone(),
+                content: None,
+                error: Some(error),
+                timed_out,
+                latency_ms,
+                usage: None,
+                tokens: None,
+            };
+
+            let Some(provider) = self.providers.iter().find(|p| p.name() == target.provider) else {
+                return failed(format!("Provider '{}' not found", target.provider), false, 0);
+            };
+
+            let provider_options = Self::provider_options(provider.as_ref(), options);
+            let started = Instant::now();
+            let call = provider.complete_with_options(messages.clone(), target.model.clone(), &provider_options);
+            let Ok(result) = tokio::time::timeout(timeout, call).await else {
+                let latency_ms = started.elapsed().as_millis() as u64;
+                let error = format!("No answer within {:?}", timeout);
+                let result = Err(AIError::ApiError(error.clone()));
+                self.record_usage(provider.as_ref(), &target.model, &result, started);
+                return failed(error, true, latency_ms);
+            };
+            let latency_ms = started.elapsed().as_millis() as u64;
+            self.record_usage(provider.as_ref(), &target.model, &result, started);
+
+            match result {
+                Ok(response) => CompareResult {
+                    provider: response.provider,
+                    model: response.model.or_else(|| target.model.clone()),
+                    content: Some(response.content),
+                    error: None,
+                    timed_out: false,
+                    latency_ms,
+                    usage: response.usage,
+                    tokens: response.tokens,
+                },
+                Err(e) => failed(e.to_string(), false, latency_ms),
+            }
+        });
+
+        join_all(calls).await
+    }
+
+    /// Build the candidate list for a request and validate the model override
+    async fn route(&self, model: Option<&str>, task: Option<&str>) -> Result<Vec<String>, AIError> {
+        let candidates = self.config.routing.route(
+            &self.list_providers(),
+            &self.default_provider,
+            task,
+            &self.latencies,
+        );
+
+        if candidates.is_empty() {
+            return Err(AIError::ProviderUnavailable(match task {
+                Some(task) => format!("No providers configured for task '{}'", task),
+                None => "No providers configured".to_string(),
+            }));
+        }
+
+        if let Some(model) = model {
+            self.validate_model(model).await?;
+        }
+
+        Ok(candidates)
+    }
+
+    /// Why a provider can't take this request right now, if it can't
+    async fn skip_reason(&self, provider: &dyn AIProvider, model: Option<&str>) -> Option<String> {
+        // Skip providers that keep failing until their cooldown ends
+        if !self.breaker.allows(provider.name()) {
+            return Some("circuit open".to_string());
+        }
+
+        if !provider.is_available().await {
+            return Some("unavailable".to_string());
+        }
+
+        // Skip providers known not to offer the requested model
+        if let Some(model) = model {
+            if !Self::offers_model(provider, model).await {
+                return Some(format!("no model '{}'", model));
+            }
+        }
+
+        None
+    }
+
+    /// Generation options for a provider (drops `response_format` if unsupported)
+    fn provider_options(provider: &dyn AIProvider, options: &GenerationOptions) -> GenerationOptions {
+        if provider.supports_structured_output() {
+            options.clone()
+        } else {
+            GenerationOptions {
+                response_format: None,
+                ..options.clone()
+            }
+        }
+    }
+
+    /// List models offered by a provider (cached by the provider)
+    pub async fn list_models(&self, provider: &str) -> Result<Vec<ModelInfo>, AIError> {
+        let provider = self
+            .providers
+            .iter()
+            .find(|p| p.name() == provider)
+            .ok_or_else(|| AIError::ProviderUnavailable(format!("Provider '{}' not found", provider)))?;
+
+        provider.list_models().await
+    }
+
+    /// Check that at least one provider could serve `model`
+    ///
+    /// Providers that can't list their models are skipped; the model is
+    /// only accepted unchecked when no provider listed any.
+    async fn validate_model(&self, model: &str) -> Result<(), AIError> {
+        let mut known: Vec<String> = Vec::new();
+
+        for provider in self.providers.iter() {
+            match provider.list_models().await {
+                Ok(models) => {
+                    if models.iter().any(|m| m.id == model) {
+                        return Ok(());
+                    }
+                    known.extend(models.into_iter().map(|m| m.id));
+                }
+                Err(e) => eprintln!("[AI] Can't list {} models: {}", provider.name(), e),
+            }
+        }
+
+        if known.is_empty() {
+            return Ok(());
+        }
+
+        Err(AIError::ModelNotFound(format!(
+            "'{}' is not offered by any provider (available: {})",
+            model,
+            known.join(", ")
+        )))
+    }
+
+    /// Check if a provider offers `model`, assuming yes when it can't tell
+    async fn offers_model(provider: &dyn AIProvider, model: &str) -> bool {
+        match provider.list_models().await {
+            Ok(models) if !models.is_empty() => models.iter().any(|m| m.id == model),
+            _ => true,
+        }
+    }
+
     /// List all available provider names
     pub fn list_providers(&self) -> Vec<String> {
         self.providers.iter().map(|p| p.name().to_string()).collect()
@@ -179,6 +588,7 @@ impl AIProviderManager {
             )));
         }
 
+        self.config.default_provider = name.clone();
         self.default_provider = name;
         Ok(())
     }
@@ -188,6 +598,26 @@ impl AIProviderManager {
         &self.default_provider
     }
 
+    /// Run the health check of every registered provider concurrently
+    ///
+    /// Providers that don't answer within `timeout` are reported unreachable.
+    pub async fn diagnose(&self, timeout: Duration) -> Vec<ProviderDiagnostics> {
+        join_all(self.providers.iter().map(|p| async move {
+            tokio::time::timeout(timeout, p.diagnose())
+                .await
+                .unwrap_or_else(|_| ProviderDiagnostics {
+                    provider: p.name().to_string(),
+                    reachable: false,
+                    auth_ok: None,
+                    model: p.configured_model(),
+                    model_available: None,
+                    latency_ms: None,
+                    error: Some(format!("No answer within {:?}", timeout)),
+                })
+        }))
+        .await
+    }
+
     /// Check if a specific provider is available
     pub async fn is_provider_available(&self, name: &str) -> bool {
         if let Some(provider) = self.providers.iter().find(|p| p.name() == name) {
@@ -197,3 +627,324 @@ impl AIProviderManager {
         }
     }
 }
+
+#[cfg(test)]
+impl AIProviderManager {
+    /// Manager over mock providers ("a" is the default), with near-instant retries
+    pub(crate) fn with_mocks(
+        providers: &[&crate::ai::testing::MockProvider],
+        routing: crate::ai::routing::RoutingPolicy,
+    ) -> Self {
+        let config = AIConfig {
+            default_provider: "a".to_string(),
+            routing,
+            ..AIConfig::default()
+        };
+        Self {
+            providers: Arc::new(providers.iter().map(|p| p.boxed()).collect()),
+            default_provider: config.default_provider.clone(),
+            config,
+            retry_policy: RetryPolicy {
+                max_attempts: 3,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+            },
+            breaker: Arc::default(),
+            latencies: Arc::default(),
+            usage: None,
+            cache: None,
+            cliproxyapi_url: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::routing::RoutingPolicy;
+    use crate::ai::testing::{http_error, user_message, MockProvider, TempDir};
+    use crate::ai::types::ResponseFormat;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn manager(providers: &[&MockProvider], routing: RoutingPolicy) -> AIProviderManager {
+        AIProviderManager::with_mocks(providers, routing)
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_default_provider_fails() {
+        let a = MockProvider::new("a").fail(AIError::ApiError("bad request".into()));
+        let b = MockProvider::new("b").reply("from b");
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        let response = manager.complete(user_message("hi")).await.unwrap();
+
+        assert_eq!(response.content, "from b");
+        assert_eq!(a.calls(), 1, "non-retryable errors are not retried");
+        let route = response.route.unwrap();
+        assert_eq!(route.candidates, vec!["a", "b"]);
+        assert_eq!(route.skipped.len(), 1);
+        assert!(route.skipped[0].starts_with("a ("));
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors_on_the_same_provider() {
+        let a = MockProvider::new("a").fail(http_error(503)).reply("recovered");
+        let b = MockProvider::new("b").reply("from b");
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        let response = manager.complete(user_message("hi")).await.unwrap();
+
+        assert_eq!(response.content, "recovered");
+        assert_eq!(a.calls(), 2);
+        assert_eq!(b.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn skips_unavailable_providers() {
+        let a = MockProvider::new("a").unavailable();
+        let b = MockProvider::new("b").reply("from b");
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        let response = manager.complete(user_message("hi")).await.unwrap();
+
+        assert_eq!(response.content, "from b");
+        assert_eq!(a.calls(), 0);
+        assert_eq!(response.route.unwrap().skipped, vec!["a (unavailable)"]);
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_when_all_providers_fail() {
+        let a = MockProvider::new("a").fail(AIError::ApiError("a down".into()));
+        let b = MockProvider::new("b").fail(AIError::ApiError("b down".into()));
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        let error = manager.complete(user_message("hi")).await.unwrap_err();
+
+        assert_eq!(error.to_string(), "API error: b down");
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_repeated_failures() {
+        let a = (0..9).fold(MockProvider::new("a"), |a, _| a.fail(http_error(500)));
+        let b = MockProvider::new("b").reply("1").reply("2").reply("3").reply("4");
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        for _ in 0..3 {
+            manager.complete(user_message("hi")).await.unwrap();
+        }
+        assert_eq!(a.calls(), 9, "three requests with three attempts each");
+
+        let response = manager.complete(user_message("hi")).await.unwrap();
+        assert_eq!(a.calls(), 9, "open circuit skips the provider");
+        assert_eq!(response.route.unwrap().skipped, vec!["a (circuit open)"]);
+    }
+
+    #[tokio::test]
+    async fn only_provider_failures_open_the_circuit() {
+        let a = (0..4).fold(MockProvider::new("a"), |a, _| a.fail(http_error(400)));
+        let a = (0..3).fold(a, |a, _| a.fail(http_error(401)));
+        let b = (0..7).fold(MockProvider::new("b"), |b, _| b.reply("from b"));
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        for _ in 0..4 {
+            manager.complete(user_message("hi")).await.unwrap();
+        }
+        assert_eq!(a.calls(), 4);
+        assert!(manager.breaker.allows("a"), "bad requests don't count");
+
+        for _ in 0..3 {
+            manager.complete(user_message("hi")).await.unwrap();
+        }
+        assert!(!manager.breaker.allows("a"), "rejected credentials do");
+    }
+
+    #[tokio::test]
+    async fn clones_share_breaker_and_latency_state() {
+        let a = (0..3).fold(MockProvider::new("a"), |a, _| a.fail(http_error(401)));
+        let b = (0..3).fold(MockProvider::new("b"), |b, _| b.reply("from b"));
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        let snapshot = manager.clone();
+        for _ in 0..3 {
+            snapshot.complete(user_message("hi")).await.unwrap();
+        }
+
+        assert!(!manager.breaker.allows("a"));
+        assert!(manager.latencies.get("b").is_some());
+    }
+
+    #[tokio::test]
+    async fn task_routes_are_exhaustive() {
+        let a = MockProvider::new("a").reply("from a");
+        let b = MockProvider::new("b").fail(AIError::ApiError("b down".into()));
+        let routing = RoutingPolicy {
+            task_routes: HashMap::from([("lyrics".to_string(), vec!["b".to_string()])]),
+            ..RoutingPolicy::default()
+        };
+        let manager = manager(&[&a, &b], routing);
+
+        let result = manager
+            .complete_for_task(user_message("hi"), None, &GenerationOptions::default(), Some("lyrics"))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(a.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn strips_response_format_for_unstructured_providers() {
+        let a = MockProvider::new("a").reply("{}");
+        let b = MockProvider::new("b").structured().reply("{}");
+        let options = GenerationOptions {
+            response_format: Some(ResponseFormat::JsonObject),
+            ..GenerationOptions::default()
+        };
+
+        let manager_a = manager(&[&a], RoutingPolicy::default());
+        manager_a.complete_with_options(user_message("hi"), None, &options).await.unwrap();
+        assert!(a.last_options().unwrap().response_format.is_none());
+
+        let manager_b = manager(&[&b], RoutingPolicy::default());
+        manager_b.complete_with_options(user_message("hi"), None, &options).await.unwrap();
+        assert!(b.last_options().unwrap().response_format.is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_models_no_provider_offers() {
+        let a = MockProvider::new("a").with_models(&["m1"]).reply("x");
+        let b = MockProvider::new("b").with_models(&["m2"]).reply("from b");
+        let manager = manager(&[&a, &b], RoutingPolicy::default());
+
+        let error = manager
+            .complete_with_model(user_message("hi"), Some("m3".into()))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AIError::ModelNotFound(_)));
+
+        let response = manager
+            .complete_with_model(user_message("hi"), Some("m2".into()))
+            .await
+            .unwrap();
+        assert_eq!(response.content, "from b");
+        assert_eq!(a.calls(), 0, "providers without the model are skipped");
+    }
+
+    #[tokio::test]
+    async fn providers_without_a_model_list_dont_vouch_for_models() {
+        let a = MockProvider::new("a").reply("from a");
+        let b = MockProvider::new("b").with_models(&["m2"]);
+        let listed = manager(&[&a, &b], RoutingPolicy::default());
+
+        let error = listed
+            .complete_with_model(user_message("hi"), Some("m3".into()))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AIError::ModelNotFound(_)));
+
+        // Nothing listed anywhere: the model can't be ruled out
+        let unlisted = manager(&[&a], RoutingPolicy::default());
+        let response = unlisted
+            .complete_with_model(user_message("hi"), Some("m3".into()))
+            .await
+            .unwrap();
+        assert_eq!(response.content, "from a");
+    }
+
+    #[tokio::test]
+    async fn serves_repeated_requests_from_cache() {
+        let dir = TempDir::new("cache");
+        let a = MockProvider::new("a").reply("fresh").reply("second");
+        let mut manager = manager(&[&a], RoutingPolicy::default());
+        manager.config.cache.enabled = true;
+        manager.set_response_cache(Arc::new(ResponseCache::new(dir.path())));
+
+        let first = manager.complete(user_message("hi")).await.unwrap();
+        let second = manager.complete(user_message("hi")).await.unwrap();
+
+        assert_eq!(second.content, first.content);
+        assert!(second.route.unwrap().cached);
+        assert_eq!(a.calls(), 1);
+
+        let bypass = GenerationOptions {
+            bypass_cache: true,
+            ..GenerationOptions::default()
+        };
+        let fresh = manager.complete_with_options(user_message("hi"), None, &bypass).await.unwrap();
+        assert_eq!(fresh.content, "second");
+    }
+
+    #[tokio::test]
+    async fn cache_misses_after_the_model_changes() {
+        let dir = TempDir::new("cache");
+        let a = MockProvider::new("a").with_model("m1").reply("from m1");
+        let mut manager = manager(&[&a], RoutingPolicy::default());
+        manager.config.cache.enabled = true;
+        manager.set_response_cache(Arc::new(ResponseCache::new(dir.path())));
+        manager.complete(user_message("hi")).await.unwrap();
+
+        let switched = MockProvider::new("a").with_model("m2").reply("from m2");
+        manager.providers = Arc::new(vec![switched.boxed()]);
+        let response = manager.complete(user_message("hi")).await.unwrap();
+
+        assert_eq!(response.content, "from m2");
+        assert!(!response.route.unwrap().cached);
+        assert_eq!(switched.calls(), 1);
+
+        manager.update_config(manager.config.clone());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn compare_reports_each_target_separately() {
+        let a = MockProvider::new("a").reply("from a");
+        let b = MockProvider::new("b").fail(http_error(500));
+        let slow = MockProvider::new("slow").delayed(Duration::from_secs(5)).reply("late");
+        let manager = manager(&[&a, &b, &slow], RoutingPolicy::default());
+
+        let target = |provider: &str, model: Option<&str>| CompareTarget {
+            provider: provider.to_string(),
+            model: model.map(str::to_string),
+        };
+        let targets = [
+            target("a", Some("m1")),
+            target("b", None),
+            target("slow", None),
+            target("missing", None),
+        ];
+
+        let results = manager
+            .compare(user_message("hi"), &targets, &GenerationOptions::default(), Duration::from_millis(100))
+            .await;
+
+        let providers: Vec<&str> = results.iter().map(|r| r.provider.as_str()).collect();
+        assert_eq!(providers, ["a", "b", "slow", "missing"]);
+
+        assert_eq!(results[0].content.as_deref(), Some("from a"));
+        assert_eq!(results[0].model.as_deref(), Some("m1"));
+        assert_eq!(results[0].usage.unwrap().total(), 15);
+
+        assert!(results[1].error.is_some() && !results[1].timed_out);
+        assert!(results[2].timed_out && results[2].content.is_none());
+        assert!(results[3].error.as_deref().unwrap().contains("not found"));
+
+        // One attempt each, no retry; the breaker is left alone
+        assert_eq!(b.calls(), 1);
+        assert!(manager.breaker.allows("b"));
+    }
+
+    #[tokio::test]
+    async fn diagnose_reports_hung_providers_as_unreachable() {
+        let a = MockProvider::new("a").with_models(&["m1"]);
+        let slow = MockProvider::new("slow").with_model("m2").delayed(Duration::from_secs(5));
+        let manager = manager(&[&a, &slow], RoutingPolicy::default());
+
+        let diagnostics = manager.diagnose(Duration::from_millis(100)).await;
+
+        assert!(diagnostics[0].reachable && diagnostics[0].error.is_none());
+        assert_eq!(diagnostics[1].provider, "slow");
+        assert!(!diagnostics[1].reachable);
+        assert_eq!(diagnostics[1].model.as_deref(), Some("m2"));
+        assert!(diagnostics[1].error.as_deref().unwrap().contains("No answer"));
+    }
+}
@@ -4,29 +4,103 @@ pub mod types;
 // AI provider trait and error types
 pub mod provider;
 
+// Secret handling: redacted strings and encrypted key vault
+pub mod secret;
+pub mod vault;
+
 // AI provider implementations
 pub mod cliproxyapi;  // CLIProxyAPI - external server (Claude/Gemini/Codex via OAuth)
 pub mod openai;       // OpenAI API - fallback option
+pub mod anthropic;    // Anthropic Messages API - API key, streaming
+pub mod claude_code;  // Claude Code CLI OAuth credentials (status and token refresh)
+pub mod gemini_cli;   // Gemini API - API key or Gemini CLI OAuth token
+
+// Model discovery helpers (/v1/models parsing and caching)
+pub mod models;
 
 // CLIProxyAPI binary manager (download, spawn, lifecycle)
 pub mod cliproxyapi_manager;
+pub mod cliproxyapi_config;
+pub mod cliproxyapi_auth;
+pub mod cliproxyapi_log;
 pub mod cliproxyapi_commands;
 
 // AI provider manager
 pub mod manager;
 
+// Retry policy and circuit breaker for provider calls
+pub mod retry;
+
+// Provider routing policy (priority lists, per-task routes, ordering)
+pub mod routing;
+
+// Persisted AI configuration (tauri-plugin-store)
+pub mod config_store;
+
 // Tauri commands for AI operations
 pub mod commands;
 
+// File helpers shared by the persisted AI stores
+pub mod storage;
+
+// Conversation persistence and context-window management
+pub mod conversations;
+pub mod conversation_commands;
+
+// On-disk response cache
+pub mod cache;
+pub mod cache_commands;
+
+// Side-by-side comparison across providers/models
+pub mod compare;
+pub mod compare_commands;
+
+// Token usage ledger and cost accounting
+pub mod usage;
+pub mod usage_commands;
+
+// Structured music generation (JSON-schema constrained notes/patterns)
+pub mod music;
+pub mod music_commands;
+
+// Prompt templates / skills (bundled and user TOML/JSON files)
+pub mod skills;
+pub mod skill_commands;
+
+// Mock provider and stub HTTP server for tests
+#[cfg(test)]
+mod testing;
+
 // Re-export commonly used types and functions
 pub use commands::{
     ai_complete,
+    ai_complete_stream,
     check_ai_provider_availability,
+    diagnose_ai_providers,
+    get_claude_code_auth_status,
+    get_ai_config,
+    get_ai_key_vault_status,
     get_ai_provider,
+    list_ai_models,
     list_ai_providers,
-    set_ai_provider
+    reset_ai_key_vault,
+    set_ai_provider,
+    update_ai_config
 };
 pub use cliproxyapi_commands::*;
-pub use cliproxyapi_manager::CLIProxyAPIManager;
+pub use conversation_commands::*;
+pub use conversations::ConversationStore;
+pub use cache::ResponseCache;
+pub use cache_commands::*;
+pub use compare_commands::*;
+pub use skill_commands::*;
+pub use skills::SkillRegistry;
+pub use usage::UsageLedger;
+pub use usage_commands::*;
+pub use music_commands::generate_music;
+pub use cliproxyapi_manager::{CLIProxyAPIManager, SupervisorPolicy};
+pub use config_store::{load_ai_config, save_ai_config};
 pub use manager::AIProviderManager;
-pub use types::{AIConfig, AIResponse, ChatMessage};
+pub use routing::{RouteInfo, RouteOrdering, RoutingPolicy};
+pub use secret::SecretString;
+pub use types::{AIConfig, AIResponse, ChatMessage, ModelInfo, TokenUsage};
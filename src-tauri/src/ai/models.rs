@@ -0,0 +1,79 @@
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::provider::AIError;
+use super::types::ModelInfo;
+
+/// How long a discovered model list stays valid
+const MODEL_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// How long a failed model list request is remembered before asking again
+const MODEL_FAILURE_TTL: Duration = Duration::from_secs(30);
+
+/// Response body of an OpenAI-compatible `/v1/models` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct ModelsResponse {
+    #[serde(default)]
+    pub data: Vec<ModelInfo>,
+}
+
+/// Time-limited cache for a provider's model list
+#[derive(Default)]
+pub(crate) struct ModelCache {
+    entry: Mutex<Option<(Instant, Vec<ModelInfo>)>>,
+    failure: Mutex<Option<(Instant, String)>>,
+}
+
+impl ModelCache {
+    /// Get the cached models if they haven't expired
+    pub fn get(&self) -> Option<Vec<ModelInfo>> {
+        let entry = self.entry.lock().unwrap();
+        entry
+            .as_ref()
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < MODEL_CACHE_TTL)
+            .map(|(_, models)| models.clone())
+    }
+
+    /// Replace the cached models
+    pub fn set(&self, models: Vec<ModelInfo>) {
+        *self.entry.lock().unwrap() = Some((Instant::now(), models));
+        *self.failure.lock().unwrap() = None;
+    }
+
+    /// Cached models, or the outcome of `fetch`
+    ///
+    /// Failures are remembered for `MODEL_FAILURE_TTL`, so a broken models
+    /// endpoint isn't asked again on every request.
+    pub async fn get_or_fetch(
+        &self,
+        fetch: impl Future<Output = Result<Vec<ModelInfo>, AIError>>,
+    ) -> Result<Vec<ModelInfo>, AIError> {
+        if let Some(models) = self.get() {
+            return Ok(models);
+        }
+        if let Some(error) = self.recent_failure() {
+            return Err(AIError::ApiError(error));
+        }
+
+        match fetch.await {
+            Ok(models) => {
+                self.set(models.clone());
+                Ok(models)
+            }
+            Err(e) => {
+                *self.failure.lock().unwrap() = Some((Instant::now(), e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    fn recent_failure(&self) -> Option<String> {
+        let failure = self.failure.lock().unwrap();
+        failure
+            .as_ref()
+            .filter(|(failed_at, _)| failed_at.elapsed() < MODEL_FAILURE_TTL)
+            .map(|(_, error)| error.clone())
+    }
+}
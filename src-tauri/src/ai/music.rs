@@ -0,0 +1,411 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::manager::AIProviderManager;
+use super::provider::AIError;
+use super::types::{ChatMessage, GenerationOptions, JsonSchemaFormat, ResponseFormat};
+
+/// How many times the AI gets to produce valid output
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Number of steps in a drum pattern (one bar of 16th notes, matches the pattern grid)
+const PATTERN_STEPS: usize = 16;
+
+/// Kind of music data to generate
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MusicKind {
+    /// Pitched notes (melody, bassline, chords)
+    Melody,
+    /// Step-sequencer drum pattern
+    DrumPattern,
+}
+
+/// Request for structured music generation
+#[derive(Debug, Clone, Deserialize)]
+pub struct MusicRequest {
+    /// What to generate
+    pub kind: MusicKind,
+    /// Free-form description (e.g., "sad piano melody in the verse")
+    pub prompt: String,
+    /// Musical key (e.g., "A minor")
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Tempo in BPM
+    #[serde(default)]
+    pub tempo: Option<u32>,
+    /// Length in bars
+    #[serde(default)]
+    pub bars: Option<u32>,
+    /// Optional model override
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// A single note; times are in beats (quarter notes) from the start
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    /// MIDI note number (0-127)
+    pub pitch: u8,
+    /// Start time in beats
+    pub start: f64,
+    /// Duration in beats
+    pub duration: f64,
+    /// MIDI velocity (1-127)
+    pub velocity: u8,
+}
+
+/// Generated melody as a list of notes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Melody {
+    pub name: Option<String>,
+    pub notes: Vec<Note>,
+}
+
+/// One instrument row of a drum pattern
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrumTrack {
+    /// Instrument name (kick, snare, hihat, ...)
+    pub instrument: String,
+    /// On/off per 16th-note step
+    pub steps: Vec<bool>,
+}
+
+/// Generated drum pattern for the step sequencer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrumPattern {
+    pub name: Option<String>,
+    pub bpm: Option<u32>,
+    pub tracks: Vec<DrumTrack>,
+}
+
+/// Validated music data returned to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MusicData {
+    Melody(Melody),
+    DrumPattern(DrumPattern),
+}
+
+/// Result of structured music generation
+#[derive(Debug, Clone, Serialize)]
+pub struct MusicResult {
+    pub data: MusicData,
+    /// Provider that produced the accepted response
+    pub provider: String,
+    /// Number of attempts needed (1 = first response was valid)
+    pub attempts: u32,
+}
+
+impl MusicKind {
+    /// JSON schema describing the expected output
+    ///
+    /// Limited to keywords strict `json_schema` mode accepts; ranges and
+    /// lengths are given as descriptions and checked by `parse`.
+    fn schema(&self) -> Value {
+        match self {
+            MusicKind::Melody => json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": ["string", "null"] },
+                    "notes": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "pitch": { "type": "integer", "description": "MIDI note number, 0-127" },
+                                "start": { "type": "number", "description": "Start in beats, >= 0" },
+                                "duration": { "type": "number", "description": "Length in beats, > 0" },
+                                "velocity": { "type": "integer", "description": "MIDI velocity, 1-127" }
+                            },
+                            "required": ["pitch", "start", "duration", "velocity"],
+                            "additionalProperties": false
+                        }
+                    }
+                },
+                "required": ["name", "notes"],
+                "additionalProperties": false
+            }),
+            MusicKind::DrumPattern => json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": ["string", "null"] },
+                    "bpm": { "type": ["integer", "null"], "description": "Tempo, 20-300" },
+                    "tracks": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "instrument": { "type": "string" },
+                                "steps": {
+                                    "type": "array",
+                                    "items": { "type": "boolean" },
+                                    "description": format!("Exactly {} steps", PATTERN_STEPS)
+                                }
+                            },
+                            "required": ["instrument", "steps"],
+                            "additionalProperties": false
+                        }
+                    }
+                },
+                "required": ["name", "bpm", "tracks"],
+                "additionalProperties": false
+            }),
+        }
+    }
+
+    fn schema_name(&self) -> &'static str {
+        match self {
+            MusicKind::Melody => "melody",
+            MusicKind::DrumPattern => "drum_pattern",
+        }
+    }
+
+    /// Parse and validate a raw AI response
+    pub(crate) fn parse(&self, content: &str) -> Result<MusicData, String> {
+        match self {
+            MusicKind::Melody => {
+                let melody: Melody = parse_json(content)?;
+                validate_melody(&melody)?;
+                Ok(MusicData::Melody(melody))
+            }
+            MusicKind::DrumPattern => {
+                let pattern: DrumPattern = parse_json(content)?;
+                validate_pattern(&pattern)?;
+                Ok(MusicData::DrumPattern(pattern))
+            }
+        }
+    }
+}
+
+/// Generate music data, retrying with the validation error until it parses
+pub async fn generate_music(
+    manager: &AIProviderManager,
+    request: MusicRequest,
+) -> Result<MusicResult, AIError> {
+    // The pattern grid holds a single bar
+    if matches!(request.kind, MusicKind::DrumPattern) && request.bars.is_some_and(|bars| bars != 1) {
+        return Err(AIError::InvalidConfig(format!(
+            "drum patterns are one bar of {} steps; bars is only supported for melodies",
+            PATTERN_STEPS
+        )));
+    }
+
+    let schema = request.kind.schema();
+    let options = GenerationOptions {
+        temperature: Some(0.8),
+        max_tokens: None,
+        response_format: Some(ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: request.kind.schema_name().to_string(),
+                schema: schema.clone(),
+                strict: true,
+            },
+        }),
+        // Sampled at 0.8 and validated per attempt, so replies shouldn't be replayed
+        bypass_cache: true,
+    };
+
+    let mut messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: format!(
+                "You are a music composition engine. Reply with a single JSON object \
+                 matching this JSON schema and nothing else (no prose, no code fences):\n{}",
+                schema
+            ),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: describe_request(&request),
+        },
+    ];
+
+    let mut last_error = String::new();
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = manager
+            .complete_with_options(messages.clone(), request.model.clone(), &options)
+            .await?;
+
+        match request.kind.parse(&response.content) {
+            Ok(data) => {
+                return Ok(MusicResult {
+                    data,
+                    provider: response.provider,
+                    attempts: attempt,
+                })
+            }
+            Err(e) => {
+                // Feed the validation error back so the model can correct itself
+                messages.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: response.content,
+                });
+                messages.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: format!(
+                        "That response was invalid: {}. Reply again with only the corrected JSON.",
+                        e
+                    ),
+                });
+                last_error = e;
+            }
+        }
+    }
+
+    Err(AIError::ApiError(format!(
+        "AI returned invalid music data after {} attempts: {}",
+        MAX_ATTEMPTS, last_error
+    )))
+}
+
+/// Build the user prompt from the request fields
+fn describe_request(request: &MusicRequest) -> String {
+    let mut parts = vec![request.prompt.clone()];
+    if let Some(key) = &request.key {
+        parts.push(format!("Key: {}", key));
+    }
+    if let Some(tempo) = request.tempo {
+        parts.push(format!("Tempo: {} BPM", tempo));
+    }
+    match request.kind {
+        MusicKind::Melody => {
+            if let Some(bars) = request.bars {
+                parts.push(format!("Length: {} bars of 4/4", bars));
+            }
+        }
+        MusicKind::DrumPattern => parts.push(format!(
+            "Use one bar of {} sixteenth-note steps per track.",
+            PATTERN_STEPS
+        )),
+    }
+    parts.join("\n")
+}
+
+/// Deserialize JSON, tolerating code fences or prose around the object
+pub(crate) fn parse_json<T: DeserializeOwned>(content: &str) -> Result<T, String> {
+    let start = content.find('{');
+    let end = content.rfind('}');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => return Err("no JSON object found".to_string()),
+    };
+
+    serde_json::from_str(json).map_err(|e| format!("JSON does not match schema: {}", e))
+}
+
+fn validate_melody(melody: &Melody) -> Result<(), String> {
+    if melody.notes.is_empty() {
+        return Err("melody has no notes".to_string());
+    }
+
+    for (i, note) in melody.notes.iter().enumerate() {
+        if note.pitch > 127 {
+            return Err(format!("note {}: pitch {} out of range 0-127", i, note.pitch));
+        }
+        if note.velocity == 0 || note.velocity > 127 {
+            return Err(format!("note {}: velocity {} out of range 1-127", i, note.velocity));
+        }
+        if !note.start.is_finite() || note.start < 0.0 {
+            return Err(format!("note {}: start must be >= 0", i));
+        }
+        if !note.duration.is_finite() || note.duration <= 0.0 {
+            return Err(format!("note {}: duration must be > 0", i));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_pattern(pattern: &DrumPattern) -> Result<(), String> {
+    if pattern.tracks.is_empty() {
+        return Err("pattern has no tracks".to_string());
+    }
+
+    if let Some(bpm) = pattern.bpm {
+        if !(20..=300).contains(&bpm) {
+            return Err(format!("bpm {} out of range 20-300", bpm));
+        }
+    }
+
+    for track in &pattern.tracks {
+        if track.steps.len() != PATTERN_STEPS {
+            return Err(format!(
+                "track '{}' has {} steps, expected {}",
+                track.instrument,
+                track.steps.len(),
+                PATTERN_STEPS
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::routing::RoutingPolicy;
+    use crate::ai::testing::{unsupported_keywords, MockProvider};
+    use crate::ai::types::AIConfig;
+
+    fn request(kind: MusicKind, bars: Option<u32>) -> MusicRequest {
+        MusicRequest {
+            kind,
+            prompt: "four on the floor".to_string(),
+            key: None,
+            tempo: None,
+            bars,
+            model: None,
+        }
+    }
+
+    #[test]
+    fn schemas_only_use_strict_mode_keywords() {
+        for kind in [MusicKind::Melody, MusicKind::DrumPattern] {
+            let found = unsupported_keywords(&kind.schema());
+            assert!(found.is_empty(), "{:?}: {:?}", kind, found);
+        }
+    }
+
+    #[test]
+    fn ranges_left_out_of_the_schema_are_still_checked() {
+        let short = r#"{"name": null, "bpm": 120, "tracks": [{"instrument": "kick", "steps": [true, false]}]}"#;
+        assert!(MusicKind::DrumPattern.parse(short).unwrap_err().contains("expected 16"));
+
+        let silent = r#"{"name": null, "notes": [{"pitch": 60, "start": 0, "duration": 0, "velocity": 90}]}"#;
+        assert!(MusicKind::Melody.parse(silent).unwrap_err().contains("duration"));
+    }
+
+    #[tokio::test]
+    async fn retries_with_the_validation_error() {
+        let out_of_range = r#"{"name": null, "notes": [{"pitch": 200, "start": 0, "duration": 1, "velocity": 90}]}"#;
+        let valid = r#"{"name": "Lead", "notes": [{"pitch": 60, "start": 0, "duration": 1, "velocity": 90}]}"#;
+        let a = MockProvider::new("a").structured().reply(out_of_range).reply(valid);
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+
+        let result = generate_music(&manager, request(MusicKind::Melody, None)).await.unwrap();
+
+        assert_eq!((result.attempts, a.calls()), (2, 2));
+        assert!(matches!(result.data, MusicData::Melody(ref melody) if melody.notes[0].pitch == 60));
+
+        let retry = a.last_messages().unwrap();
+        assert_eq!(retry.len(), 4);
+        assert_eq!(retry[2].content, out_of_range);
+        assert!(retry[3].content.contains("pitch 200 out of range"), "{}", retry[3].content);
+        assert!(a.last_options().unwrap().bypass_cache);
+    }
+
+    #[tokio::test]
+    async fn rejects_multi_bar_drum_patterns() {
+        let manager = AIProviderManager::new(AIConfig::default());
+
+        let error = generate_music(&manager, request(MusicKind::DrumPattern, Some(4)))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, AIError::InvalidConfig(_)));
+    }
+}
@@ -0,0 +1,17 @@
+use tauri::State;
+use tokio::sync::Mutex;
+
+use super::manager::AIProviderManager;
+use super::music::{self, MusicRequest, MusicResult};
+
+/// Tauri command to generate schema-validated notes or drum patterns
+#[tauri::command]
+pub async fn generate_music(
+    request: MusicRequest,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<MusicResult, String> {
+    let manager = state.lock().await.clone();
+    music::generate_music(&manager, request)
+        .await
+        .map_err(|e| e.to_string())
+}
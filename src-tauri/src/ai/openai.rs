@@ -1,20 +1,27 @@
 use async_trait::async_trait;
 use reqwest::Client;
+use std::time::Instant;
 
-use super::provider::{AIError, AIProvider};
-use super::types::{AIResponse, ChatCompletionRequest, ChatCompletionResponse, ChatMessage};
+use super::models::{ModelCache, ModelsResponse};
+use super::provider::{AIError, AIProvider, ProviderDiagnostics};
+use super::secret::SecretString;
+use super::types::{
+    AIResponse, ChatCompletionRequest, ChatCompletionResponse, ChatMessage, GenerationOptions,
+    ModelInfo,
+};
 
 /// OpenAI AI provider
 pub struct OpenAIProvider {
     client: Client,
     base_url: String,
     model: String,
-    api_key: String,
+    api_key: SecretString,
+    models: ModelCache,
 }
 
 impl OpenAIProvider {
     /// Create a new OpenAI provider
-    pub fn new(api_key: String, model: Option<String>) -> Result<Self, AIError> {
+    pub fn new(api_key: SecretString, model: Option<String>) -> Result<Self, AIError> {
         if api_key.is_empty() {
             return Err(AIError::InvalidConfig(
                 "OpenAI API key is required".to_string(),
@@ -26,6 +33,7 @@ impl OpenAIProvider {
             base_url: "https://api.openai.com/v1".to_string(),
             model: model.unwrap_or_else(|| "gpt-4o-mini".to_string()),
             api_key,
+            models: ModelCache::default(),
         })
     }
 
@@ -33,34 +41,72 @@ impl OpenAIProvider {
     fn endpoint_url(&self) -> String {
         format!("{}/chat/completions", self.base_url.trim_end_matches('/'))
     }
+
+    /// Get the full endpoint URL for listing models
+    fn models_url(&self) -> String {
+        format!("{}/models", self.base_url.trim_end_matches('/'))
+    }
+
+    /// Request the model list, bypassing the cache
+    async fn fetch_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        let response = self
+            .client
+            .get(self.models_url())
+            .bearer_auth(self.api_key.expose())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(AIError::from_response("OpenAI models", response, Some(&self.api_key)).await);
+        }
+
+        let body: ModelsResponse = response.json().await?;
+        Ok(body.data)
+    }
 }
 
 #[async_trait]
 impl AIProvider for OpenAIProvider {
     async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
+        self.complete_with_model(messages, None).await
+    }
+
+    async fn complete_with_model(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+    ) -> Result<AIResponse, AIError> {
+        self.complete_with_options(messages, model, &GenerationOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        let model_to_use = model.unwrap_or_else(|| self.model.clone());
+
         let request = ChatCompletionRequest {
-            model: self.model.clone(),
+            model: model_to_use.clone(),
             messages,
-            temperature: Some(0.7),
-            max_tokens: Some(1000),
+            temperature: Some(options.temperature.unwrap_or(0.7)),
+            max_tokens: Some(options.max_tokens.unwrap_or(1000)),
+            response_format: options.response_format.clone(),
         };
 
         let response = self
             .client
             .post(&self.endpoint_url())
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()))
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AIError::ApiError(format!(
-                "OpenAI API error ({}): {}",
-                status, error_text
-            )));
+            return Err(AIError::from_response("OpenAI API", response, Some(&self.api_key)).await);
         }
 
         let completion: ChatCompletionResponse = response.json().await?;
@@ -73,7 +119,10 @@ impl AIProvider for OpenAIProvider {
         Ok(AIResponse {
             content: choice.message.content.clone(),
             provider: "openai".to_string(),
-            tokens: completion.usage.and_then(|u| u.total_tokens),
+            tokens: completion.usage.as_ref().and_then(|u| u.total_tokens),
+            model: Some(model_to_use),
+            usage: completion.usage.as_ref().and_then(|u| u.token_usage()),
+            route: None,
         })
     }
 
@@ -81,9 +130,168 @@ impl AIProvider for OpenAIProvider {
         "openai"
     }
 
+    fn configured_model(&self) -> Option<String> {
+        Some(self.model.clone())
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
     async fn is_available(&self) -> bool {
         // OpenAI is considered available if we have an API key
         // We don't ping the API to avoid unnecessary charges
         !self.api_key.is_empty()
     }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        self.models.get_or_fetch(self.fetch_models()).await
+    }
+
+    async fn diagnose(&self) -> ProviderDiagnostics {
+        let started = Instant::now();
+        let result = self.fetch_models().await;
+        if let Ok(models) = &result {
+            self.models.set(models.clone());
+        }
+        ProviderDiagnostics::from_models(self.name(), Some(&self.model), result, started)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::{
+        gemini_generate, models_list, openai_completion, openai_error, user_message, StubResponse,
+        StubServer,
+    };
+    use crate::ai::types::ResponseFormat;
+
+    const KEY: &str = "sk-test-secret-key";
+
+    fn provider(server: &StubServer) -> OpenAIProvider {
+        let mut provider = OpenAIProvider::new(SecretString::new(KEY), None).unwrap();
+        provider.base_url = server.url().to_string();
+        provider
+    }
+
+    #[test]
+    fn requires_an_api_key() {
+        assert!(OpenAIProvider::new(SecretString::new("  "), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_bearer_auth_and_options() {
+        let server = StubServer::start().await;
+        server.on("POST", "/chat/completions", StubResponse::json(200, openai_completion("Am", "gpt-4o-mini")));
+
+        let options = GenerationOptions {
+            temperature: Some(0.2),
+            max_tokens: Some(64),
+            response_format: Some(ResponseFormat::JsonObject),
+            ..GenerationOptions::default()
+        };
+        let response = provider(&server)
+            .complete_with_options(user_message("relative minor of C?"), None, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "Am");
+        assert_eq!(response.provider, "openai");
+        assert_eq!(response.model.as_deref(), Some("gpt-4o-mini"));
+
+        let request = &server.requests_to("/chat/completions")[0];
+        assert_eq!(request.headers["authorization"], format!("Bearer {}", KEY));
+        let body = request.json();
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["max_tokens"], 64);
+        assert_eq!(body["response_format"]["type"], "json_object");
+    }
+
+    #[tokio::test]
+    async fn error_messages_redact_the_api_key() {
+        let server = StubServer::start().await;
+        server.on(
+            "POST",
+            "/chat/completions",
+            StubResponse::json(401, openai_error(&format!("Incorrect API key provided: {}", KEY))),
+        );
+
+        let error = provider(&server).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(matches!(error, AIError::Http { status: 401, .. }));
+        assert!(!error.to_string().contains(KEY));
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retryable() {
+        let server = StubServer::start().await;
+        server.on("POST", "/chat/completions", StubResponse::json(502, openai_error("bad gateway")));
+
+        let error = provider(&server).complete(user_message("hi")).await.unwrap_err();
+
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn rejects_gemini_shaped_bodies() {
+        let server = StubServer::start().await;
+        server.on("POST", "/chat/completions", StubResponse::json(200, gemini_generate("hi")));
+
+        assert!(provider(&server).complete(user_message("hi")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn lists_and_caches_models() {
+        let server = StubServer::start().await;
+        server.on("GET", "/models", StubResponse::json(200, models_list(&["gpt-4o", "gpt-4o-mini"])));
+
+        let provider = provider(&server);
+        let models = provider.list_models().await.unwrap();
+        provider.list_models().await.unwrap();
+
+        assert_eq!(models.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["gpt-4o", "gpt-4o-mini"]);
+        assert_eq!(server.requests_to("/models").len(), 1);
+        assert_eq!(server.requests_to("/models")[0].headers["authorization"], format!("Bearer {}", KEY));
+    }
+
+    #[tokio::test]
+    async fn remembers_failed_model_lists_briefly() {
+        let server = StubServer::start().await;
+        server.on("GET", "/models", StubResponse::json(503, openai_error("overloaded")));
+
+        let provider = provider(&server);
+        assert!(provider.list_models().await.is_err());
+        assert!(provider.list_models().await.is_err());
+
+        assert_eq!(server.requests_to("/models").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn diagnose_reports_rejected_credentials() {
+        let server = StubServer::start().await;
+        server.on("GET", "/models", StubResponse::json(401, openai_error("Incorrect API key provided")));
+
+        let report = provider(&server).diagnose().await;
+
+        assert!(report.reachable);
+        assert_eq!(report.auth_ok, Some(false));
+        assert_eq!(report.model.as_deref(), Some("gpt-4o-mini"));
+        assert!(report.error.unwrap().contains("401"));
+    }
+
+    #[tokio::test]
+    async fn diagnose_bypasses_the_model_cache() {
+        let server = StubServer::start().await;
+        server.on("GET", "/models", StubResponse::json(200, models_list(&["gpt-4o-mini"])));
+        let provider = provider(&server);
+
+        provider.list_models().await.unwrap();
+        let report = provider.diagnose().await;
+
+        assert_eq!(report.auth_ok, Some(true));
+        assert_eq!(report.model_available, Some(true));
+        assert!(report.error.is_none());
+        assert_eq!(server.requests_to("/models").len(), 2);
+    }
 }
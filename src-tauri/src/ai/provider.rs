@@ -1,7 +1,11 @@
 use async_trait::async_trait;
+use reqwest::header::RETRY_AFTER;
+use serde::Serialize;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
-use super::types::{AIResponse, ChatMessage};
+use super::secret::SecretString;
+use super::types::{AIResponse, ChatMessage, GenerationOptions, ModelInfo};
 
 /// Error types for AI provider operations
 #[derive(Debug, Error)]
@@ -18,10 +22,144 @@ pub enum AIError {
     #[error("API error: {0}")]
     ApiError(String),
 
+    #[error("{message}")]
+    Http {
+        status: u16,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Model not available: {0}")]
+    ModelNotFound(String),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 }
 
+impl AIError {
+    /// Build an error from a non-success HTTP response
+    ///
+    /// Reads the body into the message (redacting `secret` if given) and
+    /// keeps the status and `Retry-After` hint for retry decisions.
+    pub(crate) async fn from_response(
+        label: &str,
+        response: reqwest::Response,
+        secret: Option<&SecretString>,
+    ) -> Self {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+
+        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        let error_text = match secret {
+            Some(secret) => secret.redact(&error_text),
+            None => error_text,
+        };
+
+        AIError::Http {
+            status: status.as_u16(),
+            message: format!("{} error ({}): {}", label, status, error_text),
+            retry_after,
+        }
+    }
+
+    /// Whether the error is transient and the same request may succeed later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AIError::Network(e) => e.is_timeout() || e.is_connect(),
+            AIError::Http { status, .. } => matches!(status, 408 | 429 | 500 | 502 | 503 | 504),
+            _ => false,
+        }
+    }
+
+    /// Whether the error points at the provider rather than the request
+    ///
+    /// Transient failures and rejected credentials count toward the circuit
+    /// breaker; a bad request or unknown model says nothing about provider health.
+    pub fn counts_as_provider_failure(&self) -> bool {
+        self.is_retryable() || matches!(self, AIError::Http { status: 401 | 403, .. })
+    }
+
+    /// Server-requested delay before retrying, if any
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AIError::Http { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Result of a provider health check
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderDiagnostics {
+    pub provider: String,
+    /// The provider answered (even if with an error status)
+    pub reachable: bool,
+    /// Credentials were accepted (`None` if the check couldn't tell)
+    pub auth_ok: Option<bool>,
+    /// Model the provider is configured to use, if any
+    pub model: Option<String>,
+    /// Whether that model is offered (`None` if the check couldn't tell)
+    pub model_available: Option<bool>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl ProviderDiagnostics {
+    /// Diagnose from the outcome of a (fresh) model list request
+    ///
+    /// An empty list means the provider can't enumerate models, so the
+    /// configured model is reported as unknown rather than missing.
+    pub fn from_models(
+        provider: &str,
+        model: Option<&str>,
+        result: Result<Vec<ModelInfo>, AIError>,
+        started: Instant,
+    ) -> Self {
+        let mut diagnostics = Self {
+            provider: provider.to_string(),
+            reachable: true,
+            auth_ok: None,
+            model: model.map(str::to_string),
+            model_available: None,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error: None,
+        };
+
+        match result {
+            Ok(models) => {
+                diagnostics.auth_ok = Some(true);
+                if !models.is_empty() {
+                    diagnostics.model_available = Some(match model {
+                        Some(model) => models.iter().any(|m| m.id == model),
+                        None => true,
+                    });
+                }
+                if diagnostics.model_available == Some(false) {
+                    diagnostics.error = Some(format!("Model '{}' is not offered", model.unwrap_or_default()));
+                }
+            }
+            Err(e) => {
+                match &e {
+                    AIError::Http { status: 401 | 403, .. } => diagnostics.auth_ok = Some(false),
+                    AIError::Network(_) | AIError::ProviderUnavailable(_) | AIError::InvalidConfig(_) => {
+                        diagnostics.reachable = false;
+                        diagnostics.latency_ms = None;
+                    }
+                    _ => {}
+                }
+                diagnostics.error = Some(e.to_string());
+            }
+        }
+
+        diagnostics
+    }
+}
+
 /// Trait for AI provider implementations
 #[async_trait]
 pub trait AIProvider: Send + Sync {
@@ -38,9 +176,68 @@ pub trait AIProvider: Send + Sync {
         self.complete(messages).await
     }
 
+    /// Generate a completion with model override and generation options
+    async fn complete_with_options(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        _options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        // Default implementation ignores generation options
+        self.complete_with_model(messages, model).await
+    }
+
+    /// Generate a completion, passing text to `on_delta` as it arrives
+    ///
+    /// Providers without streaming support deliver the whole reply as one delta.
+    async fn complete_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+        on_delta: &(dyn for<'a> Fn(&'a str) + Send + Sync),
+    ) -> Result<AIResponse, AIError> {
+        let response = self.complete_with_options(messages, model, options).await?;
+        on_delta(&response.content);
+        Ok(response)
+    }
+
+    /// Whether this provider honors `response_format` (JSON mode / JSON schema)
+    fn supports_structured_output(&self) -> bool {
+        false
+    }
+
     /// Get the name of this provider
     fn name(&self) -> &str;
 
+    /// Model used when no override is given (`None` if the provider picks one itself)
+    fn configured_model(&self) -> Option<String> {
+        None
+    }
+
     /// Check if this provider is available and configured
     async fn is_available(&self) -> bool;
+
+    /// List models offered by this provider
+    ///
+    /// An empty list means the provider can't enumerate its models,
+    /// so any model override is passed through unchecked.
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        Ok(Vec::new())
+    }
+
+    /// Check reachability, credentials and the configured model
+    ///
+    /// The default relies on `is_available` and `list_models`; HTTP
+    /// providers override it to query the server uncached.
+    async fn diagnose(&self) -> ProviderDiagnostics {
+        let started = Instant::now();
+        if !self.is_available().await {
+            let error = AIError::ProviderUnavailable(self.name().to_string());
+            return ProviderDiagnostics::from_models(self.name(), None, Err(error), started);
+        }
+
+        let result = self.list_models().await;
+        ProviderDiagnostics::from_models(self.name(), None, result, started)
+    }
 }
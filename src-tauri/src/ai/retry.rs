@@ -0,0 +1,115 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Exponential backoff policy for retrying transient provider errors
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts per provider, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Upper bound for any single delay
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (1 = first retry)
+    ///
+    /// Honors the server's `Retry-After` when given; returns `None` if that
+    /// exceeds `max_delay`, meaning it's better to fall back than to wait.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+
+        // Equal jitter: half the exponential delay plus a random share of the other half
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        let half = exp / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        Some(half + Duration::from_millis(jitter))
+    }
+}
+
+/// Per-provider failure tracking
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Circuit breaker that skips providers which keep failing
+///
+/// After `failure_threshold` consecutive failures the provider is skipped
+/// for `cooldown`. Once the cooldown passes requests are let through
+/// again; the next failure re-opens the circuit, a success closes it.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    states: Mutex<HashMap<String, BreakerState>>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(60))
+    }
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check if a request to the provider is allowed right now
+    pub fn allows(&self, provider: &str) -> bool {
+        let states = self.states.lock().unwrap();
+        match states.get(provider).and_then(|s| s.open_until) {
+            Some(open_until) => Instant::now() >= open_until,
+            None => true,
+        }
+    }
+
+    /// Record a successful request, closing the circuit
+    pub fn record_success(&self, provider: &str) {
+        self.states.lock().unwrap().remove(provider);
+    }
+
+    /// Record a failed request, opening the circuit past the threshold
+    pub fn record_failure(&self, provider: &str) {
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(provider.to_string()).or_default();
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+            eprintln!(
+                "[AI] Circuit open for {} ({} failures, cooling down {:?})",
+                provider, state.consecutive_failures, self.cooldown
+            );
+        }
+    }
+
+    /// Forget all failure history
+    pub fn reset(&self) {
+        self.states.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How candidate providers are ordered within a route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteOrdering {
+    /// Keep the configured order
+    #[default]
+    Priority,
+    /// Cheapest first, using `provider_costs`
+    Cost,
+    /// Fastest first, using observed response latency (unmeasured providers last)
+    Latency,
+}
+
+/// Policy deciding which providers handle a request and in what order
+///
+/// Route selection: a matching `task_routes` entry wins, then `priority`,
+/// then the default provider followed by the rest. Explicit lists are
+/// exhaustive - providers not named are not tried.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingPolicy {
+    /// Explicit provider order (empty = default provider first, then the rest)
+    pub priority: Vec<String>,
+    /// Per-task provider lists (e.g., "lyrics" -> ["cliproxyapi"])
+    pub task_routes: HashMap<String, Vec<String>>,
+    /// Only try the first provider of the route
+    pub no_fallback: bool,
+    /// Ordering strategy applied to the route
+    pub ordering: RouteOrdering,
+    /// Relative cost per provider, used by `RouteOrdering::Cost`
+    pub provider_costs: HashMap<String, f64>,
+}
+
+/// Route chosen for a request, reported back in `AIResponse`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteInfo {
+    /// Task the request was routed for, if any
+    pub task: Option<String>,
+    /// Providers in the order they were considered
+    pub candidates: Vec<String>,
+    /// Providers skipped or failed before the selected one, with reasons
+    pub skipped: Vec<String>,
+    /// Response was served from the cache
+    #[serde(default)]
+    pub cached: bool,
+}
+
+impl RoutingPolicy {
+    /// Build the ordered provider list for a request
+    ///
+    /// `available` is the registration order of configured providers.
+    pub fn route(
+        &self,
+        available: &[String],
+        default_provider: &str,
+        task: Option<&str>,
+        latencies: &LatencyTracker,
+    ) -> Vec<String> {
+        let explicit = task
+            .and_then(|t| self.task_routes.get(t))
+            .filter(|route| !route.is_empty())
+            .or_else(|| (!self.priority.is_empty()).then_some(&self.priority));
+
+        let ordered: Vec<&String> = match explicit {
+            Some(list) => list.iter().filter(|name| available.contains(name)).collect(),
+            None => available
+                .iter()
+                .filter(|name| name.as_str() == default_provider)
+                .chain(available.iter().filter(|name| name.as_str() != default_provider))
+                .collect(),
+        };
+
+        let mut route: Vec<String> = Vec::new();
+        for name in ordered {
+            if !route.contains(name) {
+                route.push(name.clone());
+            }
+        }
+
+        // Stable sorts keep the configured order among equals
+        match self.ordering {
+            RouteOrdering::Priority => {}
+            RouteOrdering::Cost => route.sort_by(|a, b| {
+                let cost = |name: &String| self.provider_costs.get(name).copied().unwrap_or(f64::MAX);
+                cost(a).total_cmp(&cost(b))
+            }),
+            // Providers that never succeeded have no latency and go last, so
+            // one that keeps failing isn't tried first every time
+            RouteOrdering::Latency => route.sort_by_key(|name| {
+                let latency = latencies.get(name);
+                (latency.is_none(), latency)
+            }),
+        }
+
+        if self.no_fallback {
+            route.truncate(1);
+        }
+
+        route
+    }
+}
+
+/// Smoothed response latency per provider
+#[derive(Default)]
+pub struct LatencyTracker {
+    latencies: Mutex<HashMap<String, Duration>>,
+}
+
+impl LatencyTracker {
+    /// Record a successful call's latency (exponential moving average)
+    pub fn record(&self, provider: &str, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        let smoothed = match latencies.get(provider) {
+            Some(previous) => previous.mul_f64(0.7) + latency.mul_f64(0.3),
+            None => latency,
+        };
+        latencies.insert(provider.to_string(), smoothed);
+    }
+
+    /// Get the smoothed latency (`None` until a call succeeded)
+    pub fn get(&self, provider: &str) -> Option<Duration> {
+        self.latencies.lock().unwrap().get(provider).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn route(policy: &RoutingPolicy, task: Option<&str>, latencies: &LatencyTracker) -> Vec<String> {
+        policy.route(&names(&["a", "b", "c"]), "b", task, latencies)
+    }
+
+    #[test]
+    fn defaults_to_the_default_provider_then_the_rest() {
+        let route = route(&RoutingPolicy::default(), None, &LatencyTracker::default());
+        assert_eq!(route, ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn explicit_lists_keep_only_available_providers_once() {
+        let policy = RoutingPolicy {
+            priority: names(&["c", "missing", "a", "c"]),
+            task_routes: HashMap::from([
+                ("lyrics".to_string(), names(&["a"])),
+                ("theory".to_string(), Vec::new()),
+            ]),
+            ..RoutingPolicy::default()
+        };
+        let latencies = LatencyTracker::default();
+
+        assert_eq!(route(&policy, None, &latencies), ["c", "a"]);
+        assert_eq!(route(&policy, Some("lyrics"), &latencies), ["a"]);
+        // Empty and unknown task routes fall back to the priority list
+        assert_eq!(route(&policy, Some("theory"), &latencies), ["c", "a"]);
+        assert_eq!(route(&policy, Some("beats"), &latencies), ["c", "a"]);
+    }
+
+    #[test]
+    fn orders_by_cost_with_unpriced_providers_last() {
+        let policy = RoutingPolicy {
+            ordering: RouteOrdering::Cost,
+            provider_costs: HashMap::from([("a".to_string(), 3.0), ("c".to_string(), 1.0)]),
+            ..RoutingPolicy::default()
+        };
+
+        assert_eq!(route(&policy, None, &LatencyTracker::default()), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn orders_by_latency_with_unmeasured_providers_last() {
+        let policy = RoutingPolicy {
+            ordering: RouteOrdering::Latency,
+            ..RoutingPolicy::default()
+        };
+        let latencies = LatencyTracker::default();
+        latencies.record("c", Duration::from_millis(300));
+        latencies.record("a", Duration::from_millis(100));
+
+        assert_eq!(route(&policy, None, &latencies), ["a", "c", "b"]);
+
+        // Smoothed, so one fast reply doesn't overtake right away
+        latencies.record("c", Duration::from_millis(10));
+        assert_eq!(route(&policy, None, &latencies), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn no_fallback_keeps_only_the_first_provider() {
+        let policy = RoutingPolicy {
+            no_fallback: true,
+            ordering: RouteOrdering::Cost,
+            provider_costs: HashMap::from([("c".to_string(), 1.0)]),
+            ..RoutingPolicy::default()
+        };
+
+        assert_eq!(route(&policy, None, &LatencyTracker::default()), ["c"]);
+    }
+}
@@ -0,0 +1,57 @@
+use serde::Deserialize;
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Placeholder shown instead of secret values in logs and errors
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A secret string (API key, OAuth token) that never leaks through `Debug`
+///
+/// The value is wiped from memory on drop. It deliberately does not
+/// implement `Serialize` so secrets cannot end up in plaintext config.
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Wrap a secret value
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// Access the raw secret (only for building request headers)
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Check if the secret is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.trim().is_empty()
+    }
+
+    /// Replace every occurrence of this secret in `text` with a placeholder
+    pub fn redact(&self, text: &str) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        text.replace(self.expose(), REDACTED)
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString({})", REDACTED)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
@@ -0,0 +1,41 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use super::manager::AIProviderManager;
+use super::skills::{self, Skill, SkillRegistry, SkillResult};
+
+/// Tauri command to list bundled and user skills
+#[tauri::command]
+pub async fn list_ai_skills(registry: State<'_, Mutex<SkillRegistry>>) -> Result<Vec<Skill>, String> {
+    Ok(registry.lock().await.list())
+}
+
+/// Tauri command to re-read skill files from disk
+#[tauri::command]
+pub async fn reload_ai_skills(registry: State<'_, Mutex<SkillRegistry>>) -> Result<Vec<Skill>, String> {
+    let mut registry = registry.lock().await;
+    registry.reload();
+    Ok(registry.list())
+}
+
+/// Tauri command to run a skill with the given variable values
+#[tauri::command]
+pub async fn run_ai_skill(
+    name: String,
+    vars: HashMap<String, Value>,
+    model: Option<String>,
+    registry: State<'_, Mutex<SkillRegistry>>,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<SkillResult, String> {
+    let skill = registry
+        .lock()
+        .await
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| format!("Skill '{}' not found", name))?;
+
+    let manager = state.lock().await.clone();
+    skills::run_skill(&manager, &skill, &vars, model).await
+}
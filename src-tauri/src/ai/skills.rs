@@ -0,0 +1,597 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::manager::AIProviderManager;
+use super::music::{parse_json, MusicKind};
+use super::routing::RouteInfo;
+use super::types::{ChatMessage, GenerationOptions, JsonSchemaFormat, ResponseFormat};
+
+/// Skills shipped with the app (user files with the same id override them)
+const BUNDLED_SKILLS: &[(&str, &str)] = &[
+    ("lyrics.toml", include_str!("../../skills/lyrics.toml")),
+    ("chords.toml", include_str!("../../skills/chords.toml")),
+    ("beats.toml", include_str!("../../skills/beats.toml")),
+];
+
+/// Song sections accepted by `VariableType::Section`
+const SECTIONS: &[&str] = &[
+    "intro", "verse", "pre-chorus", "chorus", "post-chorus", "bridge", "hook", "breakdown", "outro",
+];
+
+/// Tempo range accepted by `VariableType::Tempo`
+const TEMPO_RANGE: std::ops::RangeInclusive<f64> = 20.0..=300.0;
+
+/// Type of a template variable, used to validate and normalize values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableType {
+    /// Free-form text
+    #[default]
+    Text,
+    /// Musical key (e.g., "C", "F# minor", "Bbm")
+    Key,
+    /// Tempo in BPM
+    Tempo,
+    /// Genre or style name
+    Genre,
+    /// Song section (verse, chorus, bridge, ...)
+    Section,
+}
+
+/// A named, typed placeholder in a skill's prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillVariable {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: VariableType,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Whether a value must be given (ignored when there's a default)
+    #[serde(default = "default_required")]
+    pub required: bool,
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Allowed values (case-insensitive); empty = any
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Where a skill was loaded from
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkillSource {
+    #[default]
+    Bundled,
+    User,
+}
+
+/// A prompt template with typed variables and default generation settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Skill {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Routing task for `AIProviderManager` (e.g., "lyrics", "theory")
+    #[serde(default)]
+    pub task: Option<String>,
+    pub system_prompt: String,
+    /// User prompt with `{{variable}}` placeholders
+    pub prompt: String,
+    #[serde(default)]
+    pub variables: Vec<SkillVariable>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// JSON schema the reply must match; enables structured output
+    #[serde(default)]
+    pub output_schema: Option<Value>,
+    /// Music data the reply is also checked as, for limits strict schemas can't express
+    #[serde(default)]
+    pub validate_as: Option<MusicKind>,
+    #[serde(default, skip_deserializing)]
+    pub source: SkillSource,
+}
+
+/// Result of running a skill
+#[derive(Debug, Clone, Serialize)]
+pub struct SkillResult {
+    pub skill: String,
+    pub content: String,
+    /// Parsed reply, for skills with an output schema
+    pub data: Option<Value>,
+    pub provider: String,
+    pub model: Option<String>,
+    pub route: Option<RouteInfo>,
+}
+
+impl Skill {
+    /// Parse a skill from TOML or JSON, chosen by file extension
+    pub fn parse(file_name: &str, content: &str) -> Result<Self, String> {
+        let skill: Skill = if file_name.ends_with(".json") {
+            serde_json::from_str(content).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(content).map_err(|e| e.to_string())?
+        };
+        skill.check()?;
+        Ok(skill)
+    }
+
+    /// Check the template only references declared variables
+    fn check(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("skill id is empty".to_string());
+        }
+
+        for placeholder in placeholders(&self.prompt).chain(placeholders(&self.system_prompt)) {
+            if !self.variables.iter().any(|v| v.name == placeholder) {
+                return Err(format!("undeclared variable '{{{{{}}}}}'", placeholder));
+            }
+        }
+
+        for variable in &self.variables {
+            if let Some(default) = &variable.default {
+                variable
+                    .validate(&Value::String(default.clone()))
+                    .map_err(|e| format!("invalid default: {}", e))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the given values and fill in the templates
+    ///
+    /// Returns the system and user prompts.
+    pub fn render(&self, vars: &HashMap<String, Value>) -> Result<(String, String), String> {
+        if let Some(unknown) = vars
+            .keys()
+            .find(|name| !self.variables.iter().any(|v| &v.name == *name))
+        {
+            return Err(format!("Skill '{}' has no variable '{}'", self.id, unknown));
+        }
+
+        let mut values: HashMap<&str, String> = HashMap::new();
+        for variable in &self.variables {
+            let value = match vars.get(&variable.name).filter(|v| !is_blank(v)) {
+                Some(value) => variable.validate(value)?,
+                None => match &variable.default {
+                    Some(default) => variable.validate(&Value::String(default.clone()))?,
+                    None if variable.required => {
+                        return Err(format!("Missing value for '{}'", variable.name))
+                    }
+                    None => String::new(),
+                },
+            };
+            values.insert(&variable.name, value);
+        }
+
+        Ok((
+            fill(&self.system_prompt, &values).trim().to_string(),
+            fill(&self.prompt, &values).trim().to_string(),
+        ))
+    }
+}
+
+impl SkillVariable {
+    /// Validate a value and normalize it to the text inserted into the prompt
+    fn validate(&self, value: &Value) -> Result<String, String> {
+        let text = match value {
+            Value::String(s) => s.trim().to_string(),
+            Value::Number(n) => n.to_string(),
+            _ => return Err(format!("'{}' must be a string or number", self.name)),
+        };
+
+        let text = match self.kind {
+            VariableType::Text | VariableType::Genre => text,
+            VariableType::Key => normalize_key(&text)
+                .ok_or_else(|| format!("'{}' is not a musical key: {}", self.name, text))?,
+            VariableType::Tempo => {
+                let bpm: f64 = text
+                    .parse()
+                    .map_err(|_| format!("'{}' must be a tempo in BPM: {}", self.name, text))?;
+                if !TEMPO_RANGE.contains(&bpm) {
+                    return Err(format!(
+                        "'{}' must be between {} and {} BPM",
+                        self.name,
+                        TEMPO_RANGE.start(),
+                        TEMPO_RANGE.end()
+                    ));
+                }
+                bpm.round().to_string()
+            }
+            VariableType::Section => {
+                let section = text.to_ascii_lowercase().replace(' ', "-");
+                if !SECTIONS.contains(&section.as_str()) {
+                    return Err(format!(
+                        "'{}' must be a song section ({}): {}",
+                        self.name,
+                        SECTIONS.join(", "),
+                        text
+                    ));
+                }
+                section
+            }
+        };
+
+        if !self.options.is_empty() && !self.options.iter().any(|o| o.eq_ignore_ascii_case(&text)) {
+            return Err(format!(
+                "'{}' must be one of: {}",
+                self.name,
+                self.options.join(", ")
+            ));
+        }
+
+        Ok(text)
+    }
+}
+
+/// Normalize a key name ("f# min" -> "F# minor"); `None` if it isn't one
+fn normalize_key(text: &str) -> Option<String> {
+    let mut chars = text.chars();
+    let letter = chars.next()?.to_ascii_uppercase();
+    if !('A'..='G').contains(&letter) {
+        return None;
+    }
+
+    let rest = chars.as_str();
+    let (accidental, rest) = match rest.chars().next() {
+        Some(c @ ('#' | '♯')) => ("#", &rest[c.len_utf8()..]),
+        Some(c @ ('b' | '♭')) => ("b", &rest[c.len_utf8()..]),
+        _ => ("", rest),
+    };
+
+    let mode = match rest.trim().to_ascii_lowercase().as_str() {
+        "" | "maj" | "major" => "major",
+        "m" | "min" | "minor" => "minor",
+        _ => return None,
+    };
+
+    Some(format!("{}{} {}", letter, accidental, mode))
+}
+
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.trim().is_empty(),
+        _ => false,
+    }
+}
+
+/// Names of `{{placeholder}}`s in a template
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split("{{")
+        .skip(1)
+        .filter_map(|part| part.split_once("}}"))
+        .map(|(name, _)| name.trim())
+}
+
+/// Replace `{{placeholder}}`s with their values
+fn fill(template: &str, values: &HashMap<&str, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        match rest[start + 2..].split_once("}}") {
+            Some((name, after)) => {
+                output.push_str(values.get(name.trim()).map(String::as_str).unwrap_or_default());
+                rest = after;
+            }
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Skills from the bundled set plus TOML/JSON files in the user skills directory
+pub struct SkillRegistry {
+    user_dir: PathBuf,
+    skills: BTreeMap<String, Skill>,
+}
+
+impl SkillRegistry {
+    /// Load bundled skills and the user's skill files from `user_dir`
+    pub fn load(user_dir: impl Into<PathBuf>) -> Self {
+        let mut registry = Self {
+            user_dir: user_dir.into(),
+            skills: BTreeMap::new(),
+        };
+        registry.reload();
+        registry
+    }
+
+    /// Re-read all skills (invalid files are logged and skipped)
+    pub fn reload(&mut self) {
+        self.skills.clear();
+
+        for (file_name, content) in BUNDLED_SKILLS {
+            match Skill::parse(file_name, content) {
+                Ok(skill) => {
+                    self.skills.insert(skill.id.clone(), skill);
+                }
+                Err(e) => eprintln!("[AI] Invalid bundled skill {}: {}", file_name, e),
+            }
+        }
+
+        for path in skill_files(&self.user_dir) {
+            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| Skill::parse(file_name, &content));
+
+            match parsed {
+                Ok(mut skill) => {
+                    skill.source = SkillSource::User;
+                    self.skills.insert(skill.id.clone(), skill);
+                }
+                Err(e) => eprintln!("[AI] Skipping skill file {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// All skills, sorted by id
+    pub fn list(&self) -> Vec<Skill> {
+        self.skills.values().cloned().collect()
+    }
+
+    /// Look up a skill by id
+    pub fn get(&self, id: &str) -> Option<&Skill> {
+        self.skills.get(id)
+    }
+}
+
+/// `.toml` and `.json` files in `dir`, sorted for deterministic overrides
+fn skill_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("toml") | Some("json")
+            )
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Render a skill and send it through the provider manager
+///
+/// Skills with an output schema request structured output and have the
+/// reply parsed into `SkillResult::data`.
+pub async fn run_skill(
+    manager: &AIProviderManager,
+    skill: &Skill,
+    vars: &HashMap<String, Value>,
+    model: Option<String>,
+) -> Result<SkillResult, String> {
+    let (mut system_prompt, prompt) = skill.render(vars)?;
+
+    let response_format = skill.output_schema.as_ref().map(|schema| {
+        // Also state the schema in the prompt for providers without structured output
+        system_prompt.push_str(&format!(
+            "\n\nReply with a single JSON object matching this JSON schema and nothing else:\n{}",
+            schema
+        ));
+        ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: skill.id.clone(),
+                schema: schema.clone(),
+                strict: true,
+            },
+        }
+    });
+
+    let options = GenerationOptions {
+        temperature: skill.temperature,
+        max_tokens: skill.max_tokens,
+        response_format,
+        ..GenerationOptions::default()
+    };
+
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+        },
+    ];
+
+    let response = manager
+        .complete_for_task(messages, model, &options, skill.task.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let invalid = |e: String| format!("Skill '{}' returned invalid output: {}", skill.id, e);
+    let data = match &skill.output_schema {
+        Some(_) => Some(parse_json::<Value>(&response.content).map_err(invalid)?),
+        None => None,
+    };
+    if let Some(kind) = skill.validate_as {
+        kind.parse(&response.content).map_err(invalid)?;
+    }
+
+    Ok(SkillResult {
+        skill: skill.id.clone(),
+        content: response.content,
+        data,
+        provider: response.provider,
+        model: response.model,
+        route: response.route,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::routing::RoutingPolicy;
+    use crate::ai::testing::{unsupported_keywords, MockProvider, TempDir};
+    use serde_json::json;
+
+    fn chords() -> Skill {
+        Skill::parse("chords.toml", BUNDLED_SKILLS[1].1).unwrap()
+    }
+
+    fn beats() -> Skill {
+        Skill::parse("beats.toml", BUNDLED_SKILLS[2].1).unwrap()
+    }
+
+    fn beat(steps: usize) -> String {
+        json!({
+            "name": "Boom bap",
+            "bpm": 90,
+            "tracks": [{ "instrument": "kick", "steps": vec![false; steps] }]
+        })
+        .to_string()
+    }
+
+    fn vars(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn bundled_skills_parse() {
+        for (file_name, content) in BUNDLED_SKILLS {
+            let skill = Skill::parse(file_name, content).unwrap_or_else(|e| panic!("{}: {}", file_name, e));
+
+            // run_skill always asks for strict structured output
+            let found = skill.output_schema.as_ref().map(unsupported_keywords).unwrap_or_default();
+            assert!(found.is_empty(), "{}: {:?}", file_name, found);
+        }
+    }
+
+    #[test]
+    fn renders_typed_variables_with_defaults() {
+        let (_, prompt) = chords()
+            .render(&vars(&[("key", json!("f# min")), ("tempo", json!(92.4))]))
+            .unwrap();
+
+        assert_eq!(
+            prompt,
+            "Suggest a chord progression for the verse of a pop song in F# minor at 92 BPM."
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let skill = chords();
+        assert!(skill.render(&vars(&[])).is_err(), "key is required");
+        assert!(skill.render(&vars(&[("key", json!("H major"))])).is_err());
+        assert!(skill
+            .render(&vars(&[("key", json!("C")), ("tempo", json!(900))]))
+            .is_err());
+        assert!(skill
+            .render(&vars(&[("key", json!("C")), ("section", json!("middle"))]))
+            .is_err());
+        assert!(skill
+            .render(&vars(&[("key", json!("C")), ("mood", json!("sad"))]))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_undeclared_placeholders() {
+        let json = r#"{ "id": "x", "name": "X", "system_prompt": "", "prompt": "{{missing}}" }"#;
+        assert!(Skill::parse("x.json", json).is_err());
+    }
+
+    #[tokio::test]
+    async fn parses_structured_skill_output() {
+        let a = MockProvider::new("a").structured().reply(&format!("```json\n{}\n```", beat(16)));
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+
+        let result = run_skill(&manager, &beats(), &vars(&[("genre", json!("hip hop"))]), None)
+            .await
+            .unwrap();
+
+        let data = result.data.unwrap();
+        assert_eq!(data["tracks"][0]["steps"].as_array().unwrap().len(), 16);
+        assert_eq!(result.provider, "a");
+
+        let system = &a.last_messages().unwrap()[0].content;
+        assert!(system.ends_with(&beats().output_schema.unwrap().to_string()), "{}", system);
+        match a.last_options().unwrap().response_format {
+            Some(ResponseFormat::JsonSchema { json_schema }) => {
+                assert_eq!(json_schema.name, "beats");
+                assert!(json_schema.strict);
+            }
+            other => panic!("expected a JSON schema, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn checks_limits_the_schema_leaves_out() {
+        let a = MockProvider::new("a").reply(&beat(8));
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+
+        let error = run_skill(&manager, &beats(), &vars(&[("genre", json!("house"))]), None)
+            .await
+            .unwrap_err();
+
+        assert!(error.contains("expected 16"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn plain_skills_return_text_only() {
+        let a = MockProvider::new("a").reply("Am - F - C - G");
+        let manager = AIProviderManager::with_mocks(&[&a], RoutingPolicy::default());
+
+        let result = run_skill(&manager, &chords(), &vars(&[("key", json!("A minor"))]), None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.content, "Am - F - C - G");
+        assert!(result.data.is_none());
+        assert!(a.last_options().unwrap().response_format.is_none());
+    }
+
+    #[test]
+    fn user_files_override_bundled_skills_and_bad_files_are_skipped() {
+        let dir = TempDir::new("skills");
+        let chords = BUNDLED_SKILLS[1].1.replace("name = \"Chord Helper\"", "name = \"My Chords\"");
+        std::fs::write(dir.join("chords.toml"), chords).unwrap();
+        std::fs::write(
+            dir.join("hooks.json"),
+            r#"{ "id": "hooks", "name": "Hooks", "system_prompt": "", "prompt": "Write a hook about {{topic}}",
+                 "variables": [{ "name": "topic" }] }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.toml"), "id = \"broken\"\nprompt = \"{{nope}}\"").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a skill").unwrap();
+
+        let mut registry = SkillRegistry::load(dir.path());
+
+        let ids: Vec<String> = registry.list().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, ["beats", "chords", "hooks", "lyrics"]);
+        let chords = registry.get("chords").unwrap();
+        assert_eq!(chords.name, "My Chords");
+        assert!(matches!(chords.source, SkillSource::User));
+        assert!(matches!(registry.get("beats").unwrap().source, SkillSource::Bundled));
+        assert!(matches!(registry.get("hooks").unwrap().source, SkillSource::User));
+
+        std::fs::remove_file(dir.join("chords.toml")).unwrap();
+        registry.reload();
+        assert_eq!(registry.get("chords").unwrap().name, "Chord Helper");
+    }
+}
@@ -0,0 +1,70 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Write bytes to a file atomically (temp file in the same dir + rename)
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    std::fs::write(&tmp_path, bytes).map_err(|e| format!("Failed to write file: {}", e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace file: {}", e))
+}
+
+/// Write a file readable only by the current user, via temp file + rename
+pub(crate) fn write_private_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    std::fs::write(&tmp_path, bytes)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Serialize a value as pretty JSON and write it atomically
+pub(crate) fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(value)
+        .map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_atomic(path, &json)
+}
+
+/// Read a JSON file, returning `None` if it doesn't exist
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| format!("Invalid file {}: {}", path.display(), e))
+}
+
+/// Current Unix time in milliseconds
+pub(crate) fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Random hex identifier for stored records
+pub(crate) fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
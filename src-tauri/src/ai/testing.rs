@@ -0,0 +1,515 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::provider::{AIError, AIProvider};
+use super::storage::new_id;
+use super::types::{AIResponse, ChatMessage, GenerationOptions, ModelInfo, TokenUsage};
+
+/// Scripted reply for `MockProvider`
+pub enum MockReply {
+    Content(String),
+    Error(AIError),
+}
+
+#[derive(Default)]
+struct MockState {
+    script: VecDeque<MockReply>,
+    calls: Vec<(Vec<ChatMessage>, Option<String>, GenerationOptions)>,
+}
+
+/// `AIProvider` that replays scripted replies and records every call
+///
+/// Clones share state, so a test can keep a handle after boxing one
+/// into `AIProviderManager`.
+#[derive(Clone)]
+pub struct MockProvider {
+    name: String,
+    available: bool,
+    structured: bool,
+    delay: Option<Duration>,
+    model: Option<String>,
+    models: Vec<ModelInfo>,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockProvider {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            available: true,
+            structured: false,
+            delay: None,
+            model: None,
+            models: Vec::new(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Queue a successful reply
+    pub fn reply(self, content: &str) -> Self {
+        self.push(MockReply::Content(content.to_string()))
+    }
+
+    /// Queue a failure
+    pub fn fail(self, error: AIError) -> Self {
+        self.push(MockReply::Error(error))
+    }
+
+    fn push(self, reply: MockReply) -> Self {
+        self.state.lock().unwrap().script.push_back(reply);
+        self
+    }
+
+    /// Report the provider as unavailable
+    pub fn unavailable(mut self) -> Self {
+        self.available = false;
+        self
+    }
+
+    /// Claim support for `response_format`
+    pub fn structured(mut self) -> Self {
+        self.structured = true;
+        self
+    }
+
+    /// Wait before answering each call (model lists included)
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Use `model` when no override is given
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    /// Advertise a model list
+    pub fn with_models(mut self, ids: &[&str]) -> Self {
+        self.models = ids
+            .iter()
+            .map(|id| ModelInfo {
+                id: id.to_string(),
+                owned_by: None,
+            })
+            .collect();
+        self
+    }
+
+    /// Number of completion calls received
+    pub fn calls(&self) -> usize {
+        self.state.lock().unwrap().calls.len()
+    }
+
+    /// Messages of the most recent call
+    pub fn last_messages(&self) -> Option<Vec<ChatMessage>> {
+        self.state.lock().unwrap().calls.last().map(|(m, _, _)| m.clone())
+    }
+
+    /// Generation options of the most recent call
+    pub fn last_options(&self) -> Option<GenerationOptions> {
+        self.state.lock().unwrap().calls.last().map(|(_, _, o)| o.clone())
+    }
+
+    /// Boxed clone, ready to hand to the manager
+    pub fn boxed(&self) -> Box<dyn AIProvider> {
+        Box::new(self.clone())
+    }
+}
+
+#[async_trait]
+impl AIProvider for MockProvider {
+    async fn complete(&self, messages: Vec<ChatMessage>) -> Result<AIResponse, AIError> {
+        self.complete_with_options(messages, None, &GenerationOptions::default())
+            .await
+    }
+
+    async fn complete_with_options(
+        &self,
+        messages: Vec<ChatMessage>,
+        model: Option<String>,
+        options: &GenerationOptions,
+    ) -> Result<AIResponse, AIError> {
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.calls.push((messages, model.clone(), options.clone()));
+
+        match state.script.pop_front() {
+            Some(MockReply::Content(content)) => Ok(AIResponse {
+                content,
+                provider: self.name.clone(),
+                tokens: Some(15),
+                model: model.or_else(|| self.model.clone()),
+                usage: Some(TokenUsage {
+                    prompt_tokens: 10,
+                    completion_tokens: 5,
+                }),
+                route: None,
+            }),
+            Some(MockReply::Error(error)) => Err(error),
+            None => Err(AIError::ApiError(format!("{}: mock script exhausted", self.name))),
+        }
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        self.structured
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn configured_model(&self) -> Option<String> {
+        self.model.clone()
+    }
+
+    async fn is_available(&self) -> bool {
+        self.available
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, AIError> {
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        Ok(self.models.clone())
+    }
+}
+
+/// Transient HTTP error (retryable)
+pub fn http_error(status: u16) -> AIError {
+    AIError::Http {
+        status,
+        message: format!("mock error ({})", status),
+        retry_after: None,
+    }
+}
+
+/// Single-message conversation
+pub fn user_message(content: &str) -> Vec<ChatMessage> {
+    vec![ChatMessage {
+        role: "user".to_string(),
+        content: content.to_string(),
+    }]
+}
+
+/// Schema keywords strict `json_schema` mode rejects, found anywhere in `schema`
+pub fn unsupported_keywords(schema: &Value) -> Vec<String> {
+    const UNSUPPORTED: &[&str] = &[
+        "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "minItems", "maxItems",
+    ];
+
+    match schema {
+        Value::Object(map) => map
+            .iter()
+            .flat_map(|(key, value)| {
+                let own = UNSUPPORTED.contains(&key.as_str()).then(|| key.clone());
+                own.into_iter().chain(unsupported_keywords(value))
+            })
+            .collect(),
+        Value::Array(items) => items.iter().flat_map(unsupported_keywords).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Empty directory under the system temp dir, removed when dropped
+///
+/// Dropping also runs when an assertion fails, so failed tests don't
+/// leave directories behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("openmusic-{}-{}", prefix, new_id()));
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Canned HTTP response
+#[derive(Clone)]
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Wait before answering (to trigger client timeouts)
+    pub delay: Option<Duration>,
+}
+
+impl StubResponse {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self::raw(status, &body.to_string())
+    }
+
+    pub fn raw(status: u16, body: &str) -> Self {
+        Self::bytes(status, body.as_bytes().to_vec())
+    }
+
+    pub fn bytes(status: u16, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body,
+            delay: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// A request received by the stub server
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Default)]
+struct StubState {
+    /// (method, path) -> queued responses; the last one repeats
+    routes: HashMap<(String, String), VecDeque<StubResponse>>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// Minimal HTTP/1.1 server on a random local port
+///
+/// Routes match on method and path (query string included). Unmatched
+/// requests get a 404. The server stops when dropped.
+pub struct StubServer {
+    url: String,
+    state: Arc<Mutex<StubState>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl StubServer {
+    pub async fn start() -> Self {
+        Self::start_on(0).await
+    }
+
+    /// Start on a given local port (0 picks a free one)
+    pub async fn start_on(port: u16) -> Self {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.expect("bind stub server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state: Arc<Mutex<StubState>> = Arc::default();
+
+        let server_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, server_state.clone()));
+            }
+        });
+
+        Self { url, state, task }
+    }
+
+    /// Base URL, e.g. "http://127.0.0.1:12345"
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Queue a response for `method path`
+    pub fn on(&self, method: &str, path: &str, response: StubResponse) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .entry((method.to_string(), path.to_string()))
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Requests received for a path
+    pub fn requests_to(&self, path: &str) -> Vec<RecordedRequest> {
+        self.requests().into_iter().filter(|r| r.path == path).collect()
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<StubState>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        state
+            .routes
+            .get_mut(&(request.method.clone(), request.path.clone()))
+            .and_then(|queue| {
+                if queue.len() > 1 {
+                    queue.pop_front()
+                } else {
+                    queue.front().cloned()
+                }
+            })
+            .unwrap_or_else(|| StubResponse::raw(404, "not found"))
+    };
+
+    if let Some(delay) = response.delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let mut head = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let body_end = buffer.len().min(header_end + length);
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&buffer[header_end..body_end]).to_string(),
+    })
+}
+
+/// OpenAI chat completion body
+pub fn openai_completion(content: &str, model: &str) -> Value {
+    json!({
+        "id": "chatcmpl-stub",
+        "object": "chat.completion",
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop"
+        }],
+        "usage": { "prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20 }
+    })
+}
+
+/// OpenAI-compatible `/v1/models` body
+pub fn models_list(ids: &[&str]) -> Value {
+    json!({
+        "object": "list",
+        "data": ids.iter().map(|id| json!({ "id": id, "object": "model", "owned_by": "stub" })).collect::<Vec<_>>()
+    })
+}
+
+/// OpenAI-style error body
+pub fn openai_error(message: &str) -> Value {
+    json!({ "error": { "message": message, "type": "invalid_request_error" } })
+}
+
+/// Anthropic Messages API body
+pub fn anthropic_message(content: &str, model: &str) -> Value {
+    json!({
+        "id": "msg_stub",
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": [{ "type": "text", "text": content }],
+        "stop_reason": "end_turn",
+        "usage": { "input_tokens": 12, "output_tokens": 8 }
+    })
+}
+
+/// Gemini `generateContent` body
+pub fn gemini_generate(content: &str) -> Value {
+    json!({
+        "candidates": [{
+            "content": { "role": "model", "parts": [{ "text": content }] },
+            "finishReason": "STOP"
+        }],
+        "usageMetadata": { "promptTokenCount": 12, "candidatesTokenCount": 8, "totalTokenCount": 20 }
+    })
+}
@@ -1,4 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::cache::CachePolicy;
+use super::routing::{RouteInfo, RoutingPolicy};
+use super::secret::SecretString;
+use super::usage::ModelPrice;
 
 /// Represents a single message in a chat conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,35 +24,134 @@ pub struct AIResponse {
     pub provider: String,
     /// Optional token count for the response
     pub tokens: Option<u32>,
+    /// Model that produced the response, if known
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Prompt/completion token breakdown, if the provider reports it
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Route taken by the provider manager (set by `AIProviderManager`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<RouteInfo>,
+}
+
+/// Token counts for a single completion
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens in the request (prompt / input)
+    pub prompt_tokens: u32,
+    /// Tokens generated (completion / output)
+    pub completion_tokens: u32,
+}
+
+impl TokenUsage {
+    /// Total tokens billed for the call
+    pub fn total(&self) -> u32 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// A model offered by a provider (OpenAI-compatible `/v1/models` entry)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Model identifier to pass as `model` (e.g., "claude-sonnet-4")
+    pub id: String,
+    /// Organization or upstream that owns the model
+    #[serde(default)]
+    pub owned_by: Option<String>,
 }
 
 /// Configuration for AI providers
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AIConfig {
-    /// The default provider to use ("cliproxyapi" or "openai")
+    /// The default provider to use ("cliproxyapi", "openai" or "anthropic")
     pub default_provider: String,
-    /// Base URL for CLIProxyAPI server (default: http://localhost:8080)
+    /// Base URL of an external CLIProxyAPI server
+    ///
+    /// When unset, the server managed by OpenMusic is used at whatever port it runs on.
     pub cliproxyapi_url: Option<String>,
     /// Model to use for CLIProxyAPI (e.g., "claude-sonnet-4", "gemini-2.0-flash")
+    ///
+    /// When unset, the first model reported by the server is used.
     pub cliproxyapi_model: Option<String>,
     /// API key for OpenAI (fallback)
-    pub openai_api_key: Option<String>,
+    ///
+    /// Kept in the encrypted key vault, never serialized with the config.
+    #[serde(skip_serializing)]
+    pub openai_api_key: Option<SecretString>,
     /// Model to use for OpenAI (default: gpt-4o-mini)
     pub openai_model: Option<String>,
+    /// API key for the Anthropic Messages API
+    ///
+    /// Kept in the encrypted key vault, never serialized with the config.
+    #[serde(skip_serializing)]
+    pub anthropic_api_key: Option<SecretString>,
+    /// Model to use for Anthropic (default: claude-sonnet-4-20250514)
+    pub anthropic_model: Option<String>,
+    /// Provider routing policy (priority, per-task routes, fallback, ordering)
+    pub routing: RoutingPolicy,
+    /// Optional per-model prices for usage cost estimates (keyed by model id)
+    pub pricing: HashMap<String, ModelPrice>,
+    /// On-disk response cache settings
+    pub cache: CachePolicy,
 }
 
 impl Default for AIConfig {
     fn default() -> Self {
         Self {
             default_provider: "cliproxyapi".to_string(),
-            cliproxyapi_url: Some("http://localhost:8080".to_string()),
-            cliproxyapi_model: Some("gpt-5".to_string()),
+            cliproxyapi_url: None,
+            cliproxyapi_model: None,
             openai_api_key: None,
             openai_model: Some("gpt-4o-mini".to_string()),
+            anthropic_api_key: None,
+            anthropic_model: Some("claude-sonnet-4-20250514".to_string()),
+            routing: RoutingPolicy::default(),
+            pricing: HashMap::new(),
+            cache: CachePolicy::default(),
         }
     }
 }
 
+/// Per-request generation settings
+///
+/// Unset fields fall back to each provider's defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    /// Sampling temperature
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Maximum tokens to generate
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Constrain output to JSON (only sent to providers that support it)
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    /// Skip the response cache for this request (not part of the cache key)
+    #[serde(default)]
+    pub bypass_cache: bool,
+}
+
+/// Structured output mode (OpenAI-compatible `response_format`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Any valid JSON object
+    JsonObject,
+    /// JSON matching the given schema
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+/// Named JSON schema for structured output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    pub schema: serde_json::Value,
+    #[serde(default)]
+    pub strict: bool,
+}
+
 /// Internal request structure for OpenAI-compatible API
 #[derive(Debug, Serialize)]
 pub(crate) struct ChatCompletionRequest {
@@ -56,6 +161,8 @@ pub(crate) struct ChatCompletionRequest {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
 }
 
 /// Internal response structure for OpenAI-compatible API
@@ -75,6 +182,20 @@ pub(crate) struct Choice {
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: Option<u32>,
+    #[serde(default)]
+    pub completion_tokens: Option<u32>,
     #[serde(default)]
     pub total_tokens: Option<u32>,
 }
+
+impl Usage {
+    /// Prompt/completion breakdown, if the server reported both
+    pub fn token_usage(&self) -> Option<TokenUsage> {
+        Some(TokenUsage {
+            prompt_tokens: self.prompt_tokens?,
+            completion_tokens: self.completion_tokens?,
+        })
+    }
+}
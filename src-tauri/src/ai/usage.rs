@@ -0,0 +1,327 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::storage::{now_millis, write_atomic};
+use super::types::TokenUsage;
+
+/// Ledger size that triggers dropping the oldest records
+const MAX_LEDGER_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Price per million tokens for a model (in any currency, e.g. USD)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    /// Cost of a call with the given token usage
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// One provider call recorded in the usage ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Call time (Unix millis)
+    pub timestamp: u64,
+    pub provider: String,
+    pub model: Option<String>,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Total reported by the provider (may exceed prompt + completion)
+    pub total_tokens: u32,
+    pub latency_ms: u64,
+    pub success: bool,
+}
+
+/// Dimension to aggregate usage by
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    /// Calendar day (UTC, "YYYY-MM-DD")
+    Day,
+    Provider,
+    Model,
+}
+
+/// Aggregated usage for one group
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageSummary {
+    pub key: String,
+    pub calls: u64,
+    pub failures: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub avg_latency_ms: u64,
+    /// Estimated cost, if every successful call in the group had a known price
+    pub estimated_cost: Option<f64>,
+}
+
+/// Append-only local usage ledger (JSON lines in the app data directory)
+///
+/// Once the file grows past `MAX_LEDGER_BYTES` the oldest records are
+/// dropped, keeping the newest ones that fill half of it.
+pub struct UsageLedger {
+    path: PathBuf,
+    max_bytes: u64,
+    write_lock: Mutex<()>,
+}
+
+impl UsageLedger {
+    /// Create a ledger backed by `path` (created on first record)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: MAX_LEDGER_BYTES,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Record a provider call
+    ///
+    /// Failures are logged, never returned: accounting must not break completions.
+    pub fn record(
+        &self,
+        provider: &str,
+        model: Option<String>,
+        usage: Option<TokenUsage>,
+        total_tokens: Option<u32>,
+        latency_ms: u64,
+        success: bool,
+    ) {
+        let usage = usage.unwrap_or_default();
+        let record = UsageRecord {
+            timestamp: now_millis(),
+            provider: provider.to_string(),
+            model,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: total_tokens.unwrap_or_else(|| usage.total()),
+            latency_ms,
+            success,
+        };
+
+        if let Err(e) = self.append(&record) {
+            eprintln!("[AI] Failed to record usage: {}", e);
+        }
+    }
+
+    fn append(&self, record: &UsageRecord) -> Result<(), String> {
+        let _guard = self.write_lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+
+        let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open usage ledger: {}", e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write usage ledger: {}", e))?;
+
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if size > self.max_bytes {
+            self.drop_oldest()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the ledger with only the newest records filling half of `max_bytes`
+    fn drop_oldest(&self) -> Result<(), String> {
+        let text = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read usage ledger: {}", e))?;
+
+        let budget = self.max_bytes / 2;
+        let mut kept_bytes = 0;
+        let mut kept: Vec<&str> = text
+            .lines()
+            .rev()
+            .take_while(|line| {
+                kept_bytes += line.len() as u64 + 1;
+                kept_bytes <= budget
+            })
+            .collect();
+        kept.reverse();
+
+        let contents: String = kept.iter().map(|line| format!("{}\n", line)).collect();
+        write_atomic(&self.path, contents.as_bytes())
+    }
+
+    /// Read all records within `[since, until)` (Unix millis, both optional)
+    pub fn records(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<UsageRecord>, String> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = std::fs::File::open(&self.path)
+            .map_err(|e| format!("Failed to open usage ledger: {}", e))?;
+
+        Ok(BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            // Skip torn or corrupt lines rather than failing the whole query
+            .filter_map(|line| serde_json::from_str::<UsageRecord>(&line).ok())
+            .filter(|r| since.is_none_or(|s| r.timestamp >= s))
+            .filter(|r| until.is_none_or(|u| r.timestamp < u))
+            .collect())
+    }
+
+    /// Aggregate usage, estimating cost from `prices` (keyed by model id)
+    pub fn summarize(
+        &self,
+        group_by: UsageGroupBy,
+        since: Option<u64>,
+        until: Option<u64>,
+        prices: &HashMap<String, ModelPrice>,
+    ) -> Result<Vec<UsageSummary>, String> {
+        // key -> (summary, total latency, running cost while every call is priced)
+        let mut groups: BTreeMap<String, (UsageSummary, u64, Option<f64>)> = BTreeMap::new();
+
+        for record in self.records(since, until)? {
+            let key = match group_by {
+                UsageGroupBy::Day => day_key(record.timestamp),
+                UsageGroupBy::Provider => record.provider.clone(),
+                UsageGroupBy::Model => record.model.clone().unwrap_or_else(|| "unknown".to_string()),
+            };
+
+            let (summary, latency_total, cost) = groups.entry(key.clone()).or_insert_with(|| {
+                let summary = UsageSummary {
+                    key,
+                    ..UsageSummary::default()
+                };
+                (summary, 0, Some(0.0))
+            });
+
+            summary.calls += 1;
+            *latency_total += record.latency_ms;
+            if !record.success {
+                summary.failures += 1;
+                continue;
+            }
+
+            summary.prompt_tokens += record.prompt_tokens as u64;
+            summary.completion_tokens += record.completion_tokens as u64;
+            summary.total_tokens += record.total_tokens as u64;
+
+            let price = record.model.as_ref().and_then(|m| prices.get(m));
+            *cost = cost.zip(price).map(|(total, price)| {
+                total
+                    + price.cost(&TokenUsage {
+                        prompt_tokens: record.prompt_tokens,
+                        completion_tokens: record.completion_tokens,
+                    })
+            });
+        }
+
+        Ok(groups
+            .into_values()
+            .map(|(mut summary, latency_total, cost)| {
+                summary.avg_latency_ms = latency_total / summary.calls.max(1);
+                summary.estimated_cost = cost;
+                summary
+            })
+            .collect())
+    }
+}
+
+/// Format a Unix millis timestamp as a UTC "YYYY-MM-DD" day
+fn day_key(timestamp_ms: u64) -> String {
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let days = (timestamp_ms / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::TempDir;
+
+    const DAY_MS: u64 = 86_400_000;
+
+    fn temp_ledger() -> (UsageLedger, TempDir) {
+        let dir = TempDir::new("usage");
+        (UsageLedger::new(dir.join("usage.jsonl")), dir)
+    }
+
+    fn record(timestamp: u64, provider: &str, model: Option<&str>, tokens: (u32, u32), success: bool) -> UsageRecord {
+        UsageRecord {
+            timestamp,
+            provider: provider.to_string(),
+            model: model.map(str::to_string),
+            prompt_tokens: tokens.0,
+            completion_tokens: tokens.1,
+            total_tokens: tokens.0 + tokens.1,
+            latency_ms: 100,
+            success,
+        }
+    }
+
+    #[test]
+    fn formats_utc_days() {
+        assert_eq!(day_key(0), "1970-01-01");
+        assert_eq!(day_key(951_782_400_000), "2000-02-29");
+        assert_eq!(day_key(1_709_251_199_999), "2024-02-29");
+        assert_eq!(day_key(1_709_251_200_000), "2024-03-01");
+    }
+
+    #[test]
+    fn summarizes_by_day_and_model_with_costs() {
+        let (ledger, _dir) = temp_ledger();
+        for r in [
+            record(0, "openai", Some("gpt-4o"), (1_000, 500), true),
+            record(1_000, "openai", Some("gpt-4o"), (0, 0), false),
+            record(DAY_MS, "anthropic", Some("claude"), (2_000, 0), true),
+        ] {
+            ledger.append(&r).unwrap();
+        }
+        let prices = HashMap::from([(
+            "gpt-4o".to_string(),
+            ModelPrice { input_per_million: 2.0, output_per_million: 8.0 },
+        )]);
+
+        let days = ledger.summarize(UsageGroupBy::Day, None, None, &prices).unwrap();
+        assert_eq!(days.iter().map(|s| s.key.as_str()).collect::<Vec<_>>(), ["1970-01-01", "1970-01-02"]);
+        assert_eq!((days[0].calls, days[0].failures, days[0].total_tokens), (2, 1, 1_500));
+        assert_eq!(days[0].estimated_cost, Some(0.006));
+        assert_eq!(days[1].estimated_cost, None, "claude has no price");
+
+        let models = ledger.summarize(UsageGroupBy::Model, Some(DAY_MS), None, &prices).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].key, "claude");
+    }
+
+    #[test]
+    fn drops_the_oldest_records_past_the_size_limit() {
+        let (mut ledger, _dir) = temp_ledger();
+        ledger.max_bytes = 2_000;
+
+        for timestamp in 0..40 {
+            ledger.append(&record(timestamp, "openai", None, (1, 1), true)).unwrap();
+        }
+
+        let size = std::fs::metadata(&ledger.path).unwrap().len();
+        assert!(size <= ledger.max_bytes, "{} bytes", size);
+        let records = ledger.records(None, None).unwrap();
+        assert_eq!(records.last().unwrap().timestamp, 39);
+        assert!(records.first().unwrap().timestamp > 0);
+        assert!(records.windows(2).all(|pair| pair[0].timestamp + 1 == pair[1].timestamp));
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use super::manager::AIProviderManager;
+use super::usage::{UsageGroupBy, UsageLedger, UsageRecord, UsageSummary};
+
+/// Tauri command to aggregate AI usage by day, provider or model
+///
+/// `since`/`until` are Unix millis. Costs use the config's price table.
+#[tauri::command]
+pub async fn ai_usage_summary(
+    group_by: UsageGroupBy,
+    since: Option<u64>,
+    until: Option<u64>,
+    ledger: State<'_, Arc<UsageLedger>>,
+    state: State<'_, Mutex<AIProviderManager>>,
+) -> Result<Vec<UsageSummary>, String> {
+    let pricing = state.lock().await.config().pricing.clone();
+    ledger.summarize(group_by, since, until, &pricing)
+}
+
+/// Tauri command to list raw usage records
+#[tauri::command]
+pub async fn ai_usage_records(
+    since: Option<u64>,
+    until: Option<u64>,
+    ledger: State<'_, Arc<UsageLedger>>,
+) -> Result<Vec<UsageRecord>, String> {
+    ledger.records(since, until)
+}
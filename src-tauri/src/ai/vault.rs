@@ -0,0 +1,466 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::secret::SecretString;
+use super::storage::write_private_atomic;
+
+/// Environment variable holding an optional vault passphrase
+pub const PASSPHRASE_ENV: &str = "OPENMUSIC_VAULT_PASSPHRASE";
+
+/// Keychain service under which machine keys are stored
+const KEYCHAIN_SERVICE: &str = "OpenMusic AI key vault";
+
+/// Current on-disk vault format version
+const VAULT_VERSION: u32 = 1;
+
+/// Error types for key vault operations
+///
+/// Messages never include secret values.
+#[derive(Debug, Error)]
+pub enum VaultError {
+    #[error("Vault I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid vault format: {0}")]
+    Format(String),
+
+    #[error("Failed to decrypt vault (wrong passphrase or different machine)")]
+    Decrypt,
+
+    #[error("Vault key not found in the OS keychain or next to the vault")]
+    KeyMissing,
+
+    #[error("Vault crypto error: {0}")]
+    Crypto(String),
+}
+
+/// How the vault encryption key is derived
+pub enum VaultUnlock {
+    /// User-supplied passphrase
+    Passphrase(SecretString),
+    /// Per-install random key kept in the OS keychain
+    MachineKey,
+}
+
+impl VaultUnlock {
+    /// Use `OPENMUSIC_VAULT_PASSPHRASE` if set, otherwise the machine key
+    pub fn from_env() -> Self {
+        match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) if !passphrase.is_empty() => {
+                Self::Passphrase(SecretString::new(passphrase))
+            }
+            _ => Self::MachineKey,
+        }
+    }
+}
+
+/// Key material for opening a vault
+struct KeyMaterial {
+    /// Material the vault is (re-)encrypted with
+    current: Vec<u8>,
+    /// Older materials the vault may still be encrypted with
+    previous: Vec<Vec<u8>>,
+    /// Key file to delete once the key lives in the keychain
+    retired_key_file: Option<PathBuf>,
+}
+
+/// Encrypted vault file layout (all binary fields base64-encoded)
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// File-based encrypted store for API keys
+///
+/// Entries are kept as a JSON map, encrypted with ChaCha20-Poly1305 using a
+/// key derived by Argon2id from a passphrase or the machine key.
+pub struct KeyVault {
+    path: PathBuf,
+    key: [u8; 32],
+    salt: [u8; 16],
+    entries: BTreeMap<String, SecretString>,
+}
+
+impl KeyVault {
+    /// Open the vault at `path`, creating an empty one if it does not exist
+    ///
+    /// A vault still encrypted with an older machine key (key file next to
+    /// the vault, or a hostname-based machine id) is re-encrypted with the
+    /// current one on open.
+    pub fn open(path: impl Into<PathBuf>, unlock: &VaultUnlock) -> Result<Self, VaultError> {
+        let path = path.into();
+        let exists = path.exists();
+        let material = Self::key_material(&path, unlock, exists)?;
+
+        if !exists {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let key = Self::derive_key(&material.current, &salt)?;
+            return Ok(Self {
+                path,
+                key,
+                salt,
+                entries: BTreeMap::new(),
+            });
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let file: VaultFile = serde_json::from_str(&content)
+            .map_err(|e| VaultError::Format(e.to_string()))?;
+
+        if file.version != VAULT_VERSION {
+            return Err(VaultError::Format(format!(
+                "Unsupported vault version {}",
+                file.version
+            )));
+        }
+
+        let salt: [u8; 16] = decode_fixed(&file.salt, "salt")?;
+        let nonce: [u8; 12] = decode_fixed(&file.nonce, "nonce")?;
+        let ciphertext = BASE64
+            .decode(&file.ciphertext)
+            .map_err(|e| VaultError::Format(format!("ciphertext: {}", e)))?;
+
+        for (i, candidate) in std::iter::once(&material.current)
+            .chain(&material.previous)
+            .enumerate()
+        {
+            let key = Self::derive_key(candidate, &salt)?;
+            let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+            let Ok(mut plaintext) = cipher.decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            else {
+                continue;
+            };
+
+            let parsed: Result<BTreeMap<String, SecretString>, _> =
+                serde_json::from_slice(&plaintext);
+            plaintext.zeroize();
+            let entries = parsed.map_err(|e| VaultError::Format(e.to_string()))?;
+
+            let mut vault = Self {
+                path,
+                key,
+                salt,
+                entries,
+            };
+            if i > 0 {
+                vault.key = Self::derive_key(&material.current, &vault.salt)?;
+                vault.save()?;
+            }
+            if let Some(retired) = &material.retired_key_file {
+                let _ = std::fs::remove_file(retired);
+            }
+            return Ok(vault);
+        }
+
+        Err(VaultError::Decrypt)
+    }
+
+    /// Get a secret by name
+    pub fn get(&self, name: &str) -> Option<SecretString> {
+        self.entries.get(name).cloned()
+    }
+
+    /// Store a secret and write the vault to disk
+    pub fn set(&mut self, name: &str, value: SecretString) -> Result<(), VaultError> {
+        self.entries.insert(name.to_string(), value);
+        self.save()
+    }
+
+    /// Remove a secret and write the vault to disk
+    pub fn remove(&mut self, name: &str) -> Result<(), VaultError> {
+        if self.entries.remove(name).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Encrypt entries and atomically replace the vault file
+    fn save(&self) -> Result<(), VaultError> {
+        let plain_map: BTreeMap<&str, &str> = self
+            .entries
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.expose()))
+            .collect();
+        let mut plaintext = serde_json::to_vec(&plain_map)
+            .map_err(|e| VaultError::Format(e.to_string()))?;
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, plaintext.as_ref());
+        plaintext.zeroize();
+        let ciphertext = ciphertext.map_err(|e| VaultError::Crypto(e.to_string()))?;
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        let json = serde_json::to_string_pretty(&file)
+            .map_err(|e| VaultError::Format(e.to_string()))?;
+
+        Ok(write_private_atomic(&self.path, json.as_bytes())?)
+    }
+
+    /// Build the raw key material for the chosen unlock method
+    ///
+    /// The machine key is a random per-install key kept in the OS keychain.
+    /// Without a usable keychain it is stored next to the vault instead,
+    /// combined with the machine id, which protects no more than the file
+    /// permissions do.
+    fn key_material(path: &Path, unlock: &VaultUnlock, exists: bool) -> Result<KeyMaterial, VaultError> {
+        if let VaultUnlock::Passphrase(passphrase) = unlock {
+            return Ok(KeyMaterial {
+                current: passphrase.expose().as_bytes().to_vec(),
+                previous: Vec::new(),
+                retired_key_file: None,
+            });
+        }
+
+        let key_path = path.with_extension("key");
+        let file_key = if key_path.exists() {
+            Some(std::fs::read(&key_path)?)
+        } else {
+            None
+        };
+        // Materials vaults were encrypted with before the keychain was used
+        let file_materials = |key: &[u8]| {
+            let mut materials = vec![with_machine_id(key, &machine_id())];
+            let legacy = with_machine_id(key, &legacy_machine_id());
+            if legacy != materials[0] {
+                materials.push(legacy);
+            }
+            materials
+        };
+
+        let account = path.display().to_string();
+        match keychain_get(&account) {
+            Ok(Some(key)) => {
+                return Ok(KeyMaterial {
+                    current: key,
+                    previous: file_key.as_deref().map(file_materials).unwrap_or_default(),
+                    retired_key_file: file_key.is_some().then_some(key_path),
+                });
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[AI] OS keychain unavailable, using key file: {}", e),
+        }
+
+        if let Some(key) = file_key {
+            // Move the key into the keychain; the vault is re-encrypted on open
+            if keychain_set(&account, &key).is_ok() {
+                return Ok(KeyMaterial {
+                    previous: file_materials(&key),
+                    current: key,
+                    retired_key_file: Some(key_path),
+                });
+            }
+            let mut materials = file_materials(&key);
+            return Ok(KeyMaterial {
+                current: materials.remove(0),
+                previous: materials,
+                retired_key_file: None,
+            });
+        }
+
+        if exists {
+            return Err(VaultError::KeyMissing);
+        }
+
+        let mut key = vec![0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        if keychain_set(&account, &key).is_ok() {
+            return Ok(KeyMaterial {
+                current: key,
+                previous: Vec::new(),
+                retired_key_file: None,
+            });
+        }
+        write_private_atomic(&key_path, &key)?;
+        Ok(KeyMaterial {
+            current: with_machine_id(&key, &machine_id()),
+            previous: Vec::new(),
+            retired_key_file: None,
+        })
+    }
+
+    /// Derive a 256-bit encryption key with Argon2id
+    fn derive_key(material: &[u8], salt: &[u8]) -> Result<[u8; 32], VaultError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(material, salt, &mut key)
+            .map_err(|e| VaultError::Crypto(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+impl Drop for KeyVault {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// Decode a base64 field into a fixed-size array
+fn decode_fixed<const N: usize>(value: &str, field: &str) -> Result<[u8; N], VaultError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| VaultError::Format(format!("{}: {}", field, e)))?
+        .try_into()
+        .map_err(|_| VaultError::Format(format!("{}: wrong length", field)))
+}
+
+/// Install key followed by a machine identifier
+fn with_machine_id(key: &[u8], id: &str) -> Vec<u8> {
+    let mut material = key.to_vec();
+    material.extend_from_slice(id.as_bytes());
+    material
+}
+
+/// Run a keychain operation on its own thread
+///
+/// Some keychain backends drive their own async runtime, which must not
+/// run inside the caller's.
+fn keychain<T: Send + 'static>(
+    account: &str,
+    op: impl FnOnce(&keyring::Entry) -> keyring::Result<T> + Send + 'static,
+) -> keyring::Result<T> {
+    let account = account.to_string();
+    std::thread::spawn(move || op(&keyring::Entry::new(KEYCHAIN_SERVICE, &account)?))
+        .join()
+        .unwrap_or_else(|_| Err(keyring::Error::PlatformFailure("keychain thread panicked".into())))
+}
+
+/// Machine key stored in the keychain for the vault `account`, if any
+fn keychain_get(account: &str) -> keyring::Result<Option<Vec<u8>>> {
+    match keychain(account, |entry| entry.get_secret()) {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn keychain_set(account: &str, key: &[u8]) -> keyring::Result<()> {
+    let key = key.to_vec();
+    keychain(account, move |entry| entry.set_secret(&key))
+}
+
+/// Stable identifier for this machine (empty if none is available)
+///
+/// Unlike the hostname, these survive renaming the machine.
+fn machine_id() -> String {
+    #[cfg(target_os = "linux")]
+    {
+        for path in ["/etc/machine-id", "/var/lib/dbus/machine-id"] {
+            if let Ok(id) = std::fs::read_to_string(path) {
+                return id.trim().to_string();
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // "IOPlatformUUID" = "XXXXXXXX-..."
+        if let Ok(output) = std::process::Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+        {
+            let text = String::from_utf8_lossy(&output.stdout);
+            if let Some(line) = text.lines().find(|l| l.contains("IOPlatformUUID")) {
+                if let Some(id) = line.split('"').nth(3) {
+                    return id.to_string();
+                }
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // "    MachineGuid    REG_SZ    xxxxxxxx-..."
+        if let Ok(output) = std::process::Command::new("reg")
+            .args(["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])
+            .output()
+        {
+            let text = String::from_utf8_lossy(&output.stdout);
+            if let Some(line) = text.lines().find(|l| l.contains("MachineGuid")) {
+                if let Some(id) = line.split_whitespace().last() {
+                    return id.to_string();
+                }
+            }
+        }
+    }
+
+    String::new()
+}
+
+/// Machine identifier used by earlier versions (hostname outside Linux)
+fn legacy_machine_id() -> String {
+    #[cfg(target_os = "linux")]
+    {
+        for path in ["/etc/machine-id", "/var/lib/dbus/machine-id"] {
+            if let Ok(id) = std::fs::read_to_string(path) {
+                return id.trim().to_string();
+            }
+        }
+    }
+
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::testing::TempDir;
+
+    fn temp_vault() -> (PathBuf, TempDir) {
+        let dir = TempDir::new("vault");
+        (dir.join("ai-keys.vault"), dir)
+    }
+
+    fn passphrase(value: &str) -> VaultUnlock {
+        VaultUnlock::Passphrase(SecretString::new(value))
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let (path, _dir) = temp_vault();
+        let mut vault = KeyVault::open(&path, &passphrase("correct horse")).unwrap();
+        vault.set("openai_api_key", SecretString::new("sk-one")).unwrap();
+        vault.set("anthropic_api_key", SecretString::new("sk-two")).unwrap();
+        vault.remove("anthropic_api_key").unwrap();
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(!file.contains("sk-one"));
+
+        let vault = KeyVault::open(&path, &passphrase("correct horse")).unwrap();
+        assert_eq!(vault.get("openai_api_key").unwrap().expose(), "sk-one");
+        assert!(vault.get("anthropic_api_key").is_none());
+    }
+
+    #[test]
+    fn wrong_key_is_reported_and_keeps_the_vault() {
+        let (path, _dir) = temp_vault();
+        let mut vault = KeyVault::open(&path, &passphrase("correct horse")).unwrap();
+        vault.set("openai_api_key", SecretString::new("sk-one")).unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let error = KeyVault::open(&path, &passphrase("battery staple")).err().unwrap();
+
+        assert!(matches!(error, VaultError::Decrypt));
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+    }
+}
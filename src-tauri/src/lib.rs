@@ -8,27 +8,45 @@ pub mod midi;
 pub mod ai;
 
 use ai::commands::{
-    ai_complete, check_ai_provider_availability, get_ai_provider, list_ai_providers,
-    set_ai_provider,
+    ai_complete, ai_complete_stream, check_ai_provider_availability, diagnose_ai_providers,
+    get_ai_config, get_ai_provider, get_claude_code_auth_status, list_ai_models,
+    list_ai_providers, set_ai_provider, update_ai_config, get_ai_key_vault_status,
+    reset_ai_key_vault,
 };
 use ai::cliproxyapi_commands::{
-    cliproxyapi_is_installed, cliproxyapi_download, cliproxyapi_start,
-    cliproxyapi_stop, cliproxyapi_is_running, cliproxyapi_get_url,
-    cliproxyapi_get_version, cliproxyapi_check_update,
+    cliproxyapi_is_installed, cliproxyapi_download, cliproxyapi_cancel_download,
+    cliproxyapi_install_from_archive, cliproxyapi_start, cliproxyapi_stop, cliproxyapi_is_running, cliproxyapi_status, cliproxyapi_get_url,
+    cliproxyapi_get_version, cliproxyapi_get_config, cliproxyapi_set_config,
+    cliproxyapi_check_update, cliproxyapi_update,
+    cliproxyapi_tail_logs, cliproxyapi_stream_logs,
+    cliproxyapi_start_login, cliproxyapi_login_status, cliproxyapi_list_accounts, cliproxyapi_logout,
 };
+use ai::conversation_commands::{
+    ai_conversation_append, ai_conversation_create, ai_conversation_delete,
+    ai_conversation_fork, ai_conversation_get, ai_conversation_list, ai_conversation_rename,
+    ai_conversation_send,
+};
+use ai::music_commands::generate_music;
+use ai::skill_commands::{list_ai_skills, reload_ai_skills, run_ai_skill};
+use ai::cache_commands::{ai_cache_clear, ai_cache_stats};
+use ai::compare_commands::ai_compare;
+use ai::usage_commands::{ai_usage_records, ai_usage_summary};
 use ai::CLIProxyAPIManager;
 use std::sync::Arc;
-use tauri::Manager;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
 use tokio::sync::Mutex;
 
+/// How long closing the window waits for the CLIProxyAPI manager before giving up on stopping it
+const CLEANUP_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Initialize AI provider manager with default configuration
-    let ai_config = ai::AIConfig::default();
-    let ai_manager = ai::AIProviderManager::new(ai_config);
-
-    // Initialize CLIProxyAPI manager (port 8080)
-    let cliproxyapi_manager = Arc::new(Mutex::new(CLIProxyAPIManager::new(8080)));
+    // Initialize CLIProxyAPI manager (moves to a free port if its own is taken)
+    let cliproxyapi_manager = CLIProxyAPIManager::new();
+    let cliproxyapi_download_cancel = cliproxyapi_manager.download_cancel();
+    let cliproxyapi_url = cliproxyapi_manager.server_url();
+    let cliproxyapi_manager = Arc::new(Mutex::new(cliproxyapi_manager));
     let cliproxyapi_for_cleanup = cliproxyapi_manager.clone();
 
     tauri::Builder::default()
@@ -36,10 +54,43 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
-        .manage(Mutex::new(ai_manager))
         .manage(Mutex::new(audio::AudioController::spawn()))
         .manage(cliproxyapi_manager)
-        .setup(|app| {
+        .manage(cliproxyapi_download_cancel)
+        .setup(move |app| {
+            let data_dir = app.path().app_data_dir()?;
+
+            // Initialize AI provider manager with persisted configuration
+            let ai_config = ai::load_ai_config(app.handle());
+            let usage_ledger = Arc::new(ai::UsageLedger::new(data_dir.join("usage.jsonl")));
+            let mut ai_manager = ai::AIProviderManager::new(ai_config);
+            ai_manager.set_cliproxyapi_url(cliproxyapi_url);
+            ai_manager.set_usage_ledger(usage_ledger.clone());
+            let response_cache = Arc::new(ai::ResponseCache::new(data_dir.join("ai-cache")));
+            ai_manager.set_response_cache(response_cache.clone());
+            app.manage(Mutex::new(ai_manager));
+            app.manage(usage_ledger);
+            app.manage(response_cache);
+
+            // Conversation history lives in the app data directory
+            app.manage(Mutex::new(ai::ConversationStore::new(data_dir.join("conversations"))));
+
+            // Bundled skills plus user skill files (override by id)
+            app.manage(Mutex::new(ai::SkillRegistry::load(data_dir.join("skills"))));
+
+            // Restart CLIProxyAPI if it crashes while it should be running
+            let supervised = app.state::<Arc<Mutex<CLIProxyAPIManager>>>().inner().clone();
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(CLIProxyAPIManager::supervise(
+                supervised,
+                ai::SupervisorPolicy::default(),
+                move |status| {
+                    if let Err(e) = handle.emit("cliproxyapi-status", status.clone()) {
+                        eprintln!("[CLIProxyAPI] Failed to emit status event: {}", e);
+                    }
+                },
+            ));
+
             #[cfg(debug_assertions)]
             {
                 let window = app.get_webview_window("main").unwrap();
@@ -50,12 +101,15 @@ pub fn run() {
         .on_window_event(move |_window, event| {
             // Kill CLIProxyAPI when app window is destroyed
             if let tauri::WindowEvent::Destroyed = event {
+                // Waits for a running command (e.g. an update) to finish, but not forever
                 let manager = cliproxyapi_for_cleanup.clone();
-                std::thread::spawn(move || {
-                    if let Ok(m) = manager.try_lock() {
-                        let _ = m.stop();
-                    }
+                let stopped = tauri::async_runtime::block_on(async move {
+                    let manager = tokio::time::timeout(CLEANUP_LOCK_TIMEOUT, manager.lock()).await.ok()?;
+                    Some(manager.stop())
                 });
+                if stopped.is_none() {
+                    eprintln!("[CLIProxyAPI] Manager still busy at exit, server left running");
+                }
             }
         })
         .invoke_handler(tauri::generate_handler![
@@ -73,19 +127,60 @@ pub fn run() {
             midi::connect_midi_output,
             // AI commands
             ai_complete,
+            ai_complete_stream,
             list_ai_providers,
             set_ai_provider,
             get_ai_provider,
             check_ai_provider_availability,
+            diagnose_ai_providers,
+            get_claude_code_auth_status,
+            get_ai_config,
+            update_ai_config,
+            get_ai_key_vault_status,
+            reset_ai_key_vault,
+            list_ai_models,
+            generate_music,
+            ai_compare,
+            // AI conversation commands
+            ai_conversation_create,
+            ai_conversation_list,
+            ai_conversation_get,
+            ai_conversation_rename,
+            ai_conversation_delete,
+            ai_conversation_append,
+            ai_conversation_fork,
+            ai_conversation_send,
+            // AI usage accounting commands
+            ai_usage_summary,
+            ai_usage_records,
+            // AI response cache commands
+            ai_cache_stats,
+            ai_cache_clear,
+            // AI skill commands
+            list_ai_skills,
+            reload_ai_skills,
+            run_ai_skill,
             // CLIProxyAPI manager commands
             cliproxyapi_is_installed,
             cliproxyapi_download,
+            cliproxyapi_cancel_download,
+            cliproxyapi_install_from_archive,
             cliproxyapi_start,
             cliproxyapi_stop,
             cliproxyapi_is_running,
+            cliproxyapi_status,
             cliproxyapi_get_url,
             cliproxyapi_get_version,
+            cliproxyapi_get_config,
+            cliproxyapi_set_config,
             cliproxyapi_check_update,
+            cliproxyapi_update,
+            cliproxyapi_tail_logs,
+            cliproxyapi_stream_logs,
+            cliproxyapi_start_login,
+            cliproxyapi_login_status,
+            cliproxyapi_list_accounts,
+            cliproxyapi_logout,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -5,10 +5,97 @@ export interface ChatMessage {
   content: string;
 }
 
+export interface RouteInfo {
+  task: string | null;
+  candidates: string[];
+  skipped: string[];
+  cached?: boolean;
+}
+
+export interface TokenUsage {
+  prompt_tokens: number;
+  completion_tokens: number;
+}
+
 export interface AIResponse {
   content: string;
   provider: string;
   tokens?: number;
+  model?: string | null;
+  usage?: TokenUsage | null;
+  route?: RouteInfo;
+}
+
+export interface RoutingPolicy {
+  priority: string[];
+  task_routes: Record<string, string[]>;
+  no_fallback: boolean;
+  ordering: 'priority' | 'cost' | 'latency';
+  provider_costs: Record<string, number>;
+}
+
+export interface ModelInfo {
+  id: string;
+  owned_by?: string | null;
+}
+
+export interface ModelPrice {
+  input_per_million: number;
+  output_per_million: number;
+}
+
+export interface CachePolicy {
+  enabled: boolean;
+  ttl_secs: number;
+  max_entries: number;
+  max_bytes: number;
+}
+
+export interface AIConfig {
+  default_provider: string;
+  // External server; unset uses the managed one wherever it runs
+  cliproxyapi_url?: string | null;
+  cliproxyapi_model?: string | null;
+  // Write-only: stored in the encrypted key vault, never returned by getConfig
+  openai_api_key?: string | null;
+  openai_model?: string | null;
+  // Write-only, like openai_api_key
+  anthropic_api_key?: string | null;
+  anthropic_model?: string | null;
+  routing?: RoutingPolicy;
+  pricing?: Record<string, ModelPrice>;
+  cache?: CachePolicy;
+}
+
+export interface MusicRequest {
+  kind: 'melody' | 'drum_pattern';
+  prompt: string;
+  key?: string;
+  tempo?: number;
+  bars?: number;
+  model?: string;
+}
+
+export interface Note {
+  pitch: number;
+  start: number;
+  duration: number;
+  velocity: number;
+}
+
+export type MusicData =
+  | { kind: 'melody'; name: string | null; notes: Note[] }
+  | {
+      kind: 'drum_pattern';
+      name: string | null;
+      bpm: number | null;
+      tracks: { instrument: string; steps: boolean[] }[];
+    };
+
+export interface MusicResult {
+  data: MusicData;
+  provider: string;
+  attempts: number;
 }
 
 export const audioApi = {
@@ -19,10 +106,176 @@ export const audioApi = {
   setVolume: (volume: number) => invoke<void>('set_volume', { volume }),
 };
 
+export interface ClaudeCodeAuthStatus {
+  logged_in: boolean;
+  expires_at: number | null; // Unix millis
+  expires_in_ms: number | null;
+  expired: boolean;
+  can_refresh: boolean;
+  needs_attention: boolean;
+  error: string | null;
+}
+
+export interface ProviderDiagnostics {
+  provider: string;
+  reachable: boolean;
+  auth_ok: boolean | null; // null: couldn't tell
+  model: string | null;
+  model_available: boolean | null;
+  latency_ms: number | null;
+  error: string | null;
+}
+
+export interface CompareTarget {
+  provider: string;
+  model?: string | null;
+}
+
+export interface CompareResult {
+  provider: string;
+  model: string | null;
+  content: string | null;
+  error: string | null;
+  timed_out: boolean;
+  latency_ms: number;
+  usage: TokenUsage | null;
+  tokens: number | null;
+}
+
 export const aiApi = {
-  complete: (messages: ChatMessage[], model?: string) => invoke<AIResponse>('ai_complete', { messages, model }),
+  complete: (messages: ChatMessage[], model?: string, task?: string, bypassCache?: boolean) =>
+    invoke<AIResponse>('ai_complete', { messages, model, task, bypassCache }),
+  // Text arrives as 'ai-stream' events ({ stream_id, delta }); resolves with the full response
+  completeStream: (messages: ChatMessage[], streamId: string, model?: string, task?: string) =>
+    invoke<AIResponse>('ai_complete_stream', { messages, model, task, streamId }),
+  // Same messages to several providers/models at once; results follow target order
+  compare: (
+    messages: ChatMessage[],
+    targets: CompareTarget[],
+    options?: { temperature?: number; maxTokens?: number; timeoutSecs?: number },
+  ) => invoke<CompareResult[]>('ai_compare', { messages, targets, ...options }),
   listProviders: () => invoke<string[]>('list_ai_providers'),
   setProvider: (name: string) => invoke<void>('set_ai_provider', { name }),
+  generateMusic: (request: MusicRequest) => invoke<MusicResult>('generate_music', { request }),
+  listModels: (provider: string) => invoke<ModelInfo[]>('list_ai_models', { provider }),
+  diagnoseProviders: () => invoke<ProviderDiagnostics[]>('diagnose_ai_providers'),
+  getConfig: () => invoke<AIConfig>('get_ai_config'),
+  getClaudeCodeAuthStatus: () => invoke<ClaudeCodeAuthStatus>('get_claude_code_auth_status'),
+  updateConfig: (config: AIConfig) => invoke<void>('update_ai_config', { config }),
+  // Why stored API keys can't be read (null when fine); resetting lets keys be entered again
+  getKeyVaultStatus: () => invoke<string | null>('get_ai_key_vault_status'),
+  resetKeyVault: () => invoke<void>('reset_ai_key_vault'),
+};
+
+export interface Conversation {
+  id: string;
+  title: string;
+  created_at: number;
+  updated_at: number;
+  parent_id: string | null;
+  messages: ChatMessage[];
+  summary: string | null;
+  summarized_count: number;
+}
+
+export interface ConversationSummary {
+  id: string;
+  title: string;
+  created_at: number;
+  updated_at: number;
+  parent_id: string | null;
+  message_count: number;
+}
+
+// Persisted AI conversations (stored in the app data directory)
+export const conversationApi = {
+  create: (title?: string) => invoke<Conversation>('ai_conversation_create', { title }),
+  list: () => invoke<ConversationSummary[]>('ai_conversation_list'),
+  get: (id: string) => invoke<Conversation>('ai_conversation_get', { id }),
+  rename: (id: string, title: string) => invoke<Conversation>('ai_conversation_rename', { id, title }),
+  delete: (id: string) => invoke<void>('ai_conversation_delete', { id }),
+  append: (id: string, messages: ChatMessage[]) =>
+    invoke<Conversation>('ai_conversation_append', { id, messages }),
+  fork: (id: string, at?: number) => invoke<Conversation>('ai_conversation_fork', { id, at }),
+  send: (id: string, message: ChatMessage, model?: string, task?: string, maxContextTokens?: number) =>
+    invoke<AIResponse>('ai_conversation_send', { id, message, model, task, maxContextTokens }),
+};
+
+export interface UsageRecord {
+  timestamp: number;
+  provider: string;
+  model: string | null;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  latency_ms: number;
+  success: boolean;
+}
+
+export interface UsageSummary {
+  key: string;
+  calls: number;
+  failures: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+  avg_latency_ms: number;
+  estimated_cost: number | null;
+}
+
+// Token usage ledger (timestamps are Unix millis)
+export const usageApi = {
+  summary: (groupBy: 'day' | 'provider' | 'model', since?: number, until?: number) =>
+    invoke<UsageSummary[]>('ai_usage_summary', { groupBy, since, until }),
+  records: (since?: number, until?: number) =>
+    invoke<UsageRecord[]>('ai_usage_records', { since, until }),
+};
+
+// On-disk AI response cache (enable via AIConfig.cache)
+export const cacheApi = {
+  stats: () => invoke<{ entries: number; bytes: number }>('ai_cache_stats'),
+  clear: () => invoke<void>('ai_cache_clear'),
+};
+
+export interface SkillVariable {
+  name: string;
+  type: 'text' | 'key' | 'tempo' | 'genre' | 'section';
+  description: string | null;
+  required: boolean;
+  default: string | null;
+  options: string[];
+}
+
+export interface AISkill {
+  id: string;
+  name: string;
+  description: string;
+  task: string | null;
+  system_prompt: string;
+  prompt: string;
+  variables: SkillVariable[];
+  temperature: number | null;
+  max_tokens: number | null;
+  output_schema: Record<string, unknown> | null;
+  validate_as: 'melody' | 'drum_pattern' | null;
+  source: 'bundled' | 'user';
+}
+
+export interface SkillResult {
+  skill: string;
+  content: string;
+  data: unknown | null;
+  provider: string;
+  model: string | null;
+  route: RouteInfo | null;
+}
+
+// Rust-side skill templates (bundled + TOML/JSON files in the app data "skills" dir)
+export const skillApi = {
+  list: () => invoke<AISkill[]>('list_ai_skills'),
+  reload: () => invoke<AISkill[]>('reload_ai_skills'),
+  run: (name: string, vars: Record<string, string | number>, model?: string) =>
+    invoke<SkillResult>('run_ai_skill', { name, vars, model }),
 };
 
 export const midiApi = {
@@ -30,16 +283,68 @@ export const midiApi = {
   listOutputPorts: () => invoke<string[]>('list_midi_output_ports'),
 };
 
+export interface DownloadProgress {
+  received: number;
+  total: number | null;
+  bytes_per_sec: number;
+}
+
+export interface CLIProxyLogLine {
+  timestamp: number; // Unix millis
+  stream: 'stdout' | 'stderr';
+  line: string;
+}
+
+export type CLIProxyServerState =
+  | 'stopped'
+  | 'starting'
+  | 'running'
+  | 'unhealthy'
+  | 'port_in_use' // another process holds the port
+  | 'restarting'
+  | 'failed'; // supervisor gave up restarting
+
+export interface CLIProxySupervisorStatus {
+  state: CLIProxyServerState;
+  restarts: number;
+  error: string | null;
+}
+
+export interface CLIProxyConfig {
+  host: string;
+  port: number;
+  log_level: 'debug' | 'info' | 'warn' | 'error';
+  auth_dir: string; // "~" is expanded
+  management: {
+    allow_remote: boolean;
+    disable_control_panel: boolean;
+  };
+}
+
 // CLIProxyAPI manager - handles download, install, and lifecycle
 export const cliproxyApi = {
   isInstalled: () => invoke<boolean>('cliproxyapi_is_installed'),
+  // Progress arrives as 'cliproxyapi-download-progress' events (DownloadProgress)
   download: () => invoke<string>('cliproxyapi_download'),
+  cancelDownload: () => invoke<void>('cliproxyapi_cancel_download'),
+  // Offline install from a local release archive (.tar.gz or .zip); resolves with the installed version
+  installFromArchive: (path: string) => invoke<string | null>('cliproxyapi_install_from_archive', { path }),
   start: () => invoke<void>('cliproxyapi_start'),
   stop: () => invoke<void>('cliproxyapi_stop'),
   isRunning: () => invoke<boolean>('cliproxyapi_is_running'),
+  // Supervisor changes arrive as 'cliproxyapi-status' events (CLIProxySupervisorStatus)
+  status: () => invoke<CLIProxyServerState>('cliproxyapi_status'),
   getUrl: () => invoke<string>('cliproxyapi_get_url'),
   getVersion: () => invoke<string | null>('cliproxyapi_get_version'),
+  getConfig: () => invoke<CLIProxyConfig>('cliproxyapi_get_config'),
+  // Rejected with all problems if invalid; a running server is restarted (rolled back if it fails)
+  setConfig: (config: CLIProxyConfig) => invoke<void>('cliproxyapi_set_config', { config }),
   checkUpdate: () => invoke<string | null>('cliproxyapi_check_update'),
+  // Installs the latest release, rolling back if it fails to start; null if already up to date
+  update: () => invoke<string | null>('cliproxyapi_update'),
+  tailLogs: (lines?: number) => invoke<CLIProxyLogLine[]>('cliproxyapi_tail_logs', { lines }),
+  // While enabled, new lines arrive as 'cliproxyapi-log' events (CLIProxyLogLine)
+  streamLogs: (enabled: boolean) => invoke<void>('cliproxyapi_stream_logs', { enabled }),
 };
 
 // CLIProxyAPI direct HTTP calls (when server is running)
@@ -55,73 +360,42 @@ export interface CLIProxyAuthFile {
   provider: string;
   label: string;
   status: string;
-  email?: string;
+  email?: string | null;
   disabled: boolean;
 }
 
 export interface OAuthUrlResponse {
-  status: string;
   url: string;
   state: string;
 }
 
-const CLIPROXY_BASE = 'http://127.0.0.1:8080';
-const CLIPROXY_MANAGEMENT_KEY = 'openmusic-local';
+// The managed server may move to another port, so ask the backend each time
+const cliproxyBase = () => cliproxyApi.getUrl();
 
 export const cliproxyHttpApi = {
   // List available models
   listModels: async (): Promise<CLIProxyModel[]> => {
-    const res = await fetch(`${CLIPROXY_BASE}/v1/models`);
+    const res = await fetch(`${await cliproxyBase()}/v1/models`);
     if (!res.ok) throw new Error(`Failed to list models: ${res.status}`);
     const data = await res.json();
     return data.data || [];
   },
 
-  // List auth accounts
-  listAuthFiles: async (): Promise<CLIProxyAuthFile[]> => {
-    const res = await fetch(`${CLIPROXY_BASE}/v0/management/auth-files`, {
-      headers: { 'X-Management-Key': CLIPROXY_MANAGEMENT_KEY }
-    });
-    if (!res.ok) {
-      if (res.status === 404) return []; // Management API not enabled
-      throw new Error(`Failed to list auth files: ${res.status}`);
-    }
-    const data = await res.json();
-    return data.files || [];
-  },
-
-  // Start OAuth login flow
-  startOAuthLogin: async (provider: 'claude' | 'gemini' | 'codex' | 'antigravity'): Promise<OAuthUrlResponse> => {
-    const endpoints: Record<string, string> = {
-      claude: '/v0/management/anthropic-auth-url?is_webui=true',
-      gemini: '/v0/management/gemini-cli-auth-url?is_webui=true',
-      codex: '/v0/management/codex-auth-url?is_webui=true',
-      antigravity: '/v0/management/antigravity-auth-url?is_webui=true',
-    };
+  // Management API calls go through the backend, which holds the per-install secret
+  listAuthFiles: () => invoke<CLIProxyAuthFile[]>('cliproxyapi_list_accounts'),
 
-    const res = await fetch(`${CLIPROXY_BASE}${endpoints[provider]}`, {
-      headers: { 'X-Management-Key': CLIPROXY_MANAGEMENT_KEY }
-    });
-    if (!res.ok) {
-      const data = await res.json().catch(() => ({}));
-      throw new Error(data.error || `Failed to start OAuth: ${res.status}`);
-    }
-    return res.json();
-  },
+  // Start OAuth login flow; open the returned URL, then poll checkOAuthStatus with its state
+  startOAuthLogin: (provider: 'claude' | 'gemini' | 'codex' | 'antigravity') =>
+    invoke<OAuthUrlResponse>('cliproxyapi_start_login', { provider }),
 
-  // Check OAuth status
-  checkOAuthStatus: async (state: string): Promise<{ status: string; error?: string }> => {
-    const res = await fetch(`${CLIPROXY_BASE}/v0/management/get-auth-status?state=${state}`, {
-      headers: { 'X-Management-Key': CLIPROXY_MANAGEMENT_KEY }
-    });
-    if (!res.ok) throw new Error(`Failed to check OAuth status: ${res.status}`);
-    return res.json();
-  },
+  // 'wait' until the user finishes in the browser, then 'ok' or 'error'
+  checkOAuthStatus: (state: string) =>
+    invoke<{ status: string; error?: string | null }>('cliproxyapi_login_status', { loginState: state }),
 
   // Health check
   isAvailable: async (): Promise<boolean> => {
     try {
-      const res = await fetch(`${CLIPROXY_BASE}/v1/models`, {
+      const res = await fetch(`${await cliproxyBase()}/v1/models`, {
         signal: AbortSignal.timeout(2000)
       });
       return res.ok;
@@ -130,15 +404,6 @@ export const cliproxyHttpApi = {
     }
   },
 
-  // Delete auth file by name
-  deleteAuthFile: async (name: string): Promise<void> => {
-    const res = await fetch(
-      `${CLIPROXY_BASE}/v0/management/auth-files?name=${encodeURIComponent(name)}`,
-      {
-        method: 'DELETE',
-        headers: { 'X-Management-Key': CLIPROXY_MANAGEMENT_KEY }
-      }
-    );
-    if (!res.ok) throw new Error(`Failed to delete auth file: ${res.status}`);
-  },
+  // Log an account out (deletes its auth file)
+  deleteAuthFile: (name: string) => invoke<void>('cliproxyapi_logout', { name }),
 };